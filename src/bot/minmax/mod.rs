@@ -1,11 +1,35 @@
 use crate::piece::Piece;
 use crate::state::{GameState, Play, Player};
+use crate::threats::{self, KingCall};
+
+use std::sync::atomic::{AtomicU32, Ordering};
 
 use rayon::prelude::*;
 
-static STARTING_DEPTH: u8 = 3;
+pub static STARTING_DEPTH: u8 = 3;
+/// The deepest search worth asking for, as each extra play ahead multiplies the time taken
+pub static MAX_DEPTH: u8 = 5;
 
 pub fn min_max_play(game_state: &GameState) -> Option<Play> {
+    min_max_search(game_state, STARTING_DEPTH).map(|(play, _)| play)
+}
+
+/// Searches the given number of plays ahead for the best play, returning it with its score.
+///
+/// Positive scores favour the attackers and negative scores the defenders, with victory for
+/// either at the extremes of an i8. The depth must be at least 1.
+pub fn min_max_search(game_state: &GameState, depth: u8) -> Option<(Play, i8)> {
+    min_max_search_with_progress(game_state, depth, &|_, _| ())
+}
+
+/// Searches like [min_max_search], calling progress with the number of top level plays
+/// searched so far and the total after each one finishes. Progress is called from the search
+/// threads, so the searched counts may arrive out of order.
+pub fn min_max_search_with_progress(
+    game_state: &GameState,
+    depth: u8,
+    progress: &(dyn Fn(u32, u32) + Sync),
+) -> Option<(Play, i8)> {
     let mut plays = game_state.available_plays();
     if plays.is_empty() {
         return None;
@@ -21,37 +45,46 @@ pub fn min_max_play(game_state: &GameState) -> Option<Play> {
         // no impact on our behaviour.
         plays.shuffle(&mut rng);
     }
-    let depth_remaining = STARTING_DEPTH;
+    let starting_depth = std::cmp::max(depth, 1);
+    let depth_remaining = starting_depth;
     let α = Heuristic(i8::MIN); // min score maximising player found (trying to maximise)
     let β = Heuristic(i8::MAX); // max score minimising player found (trying to minimise)
     // Min Max algorithm is the maximising player if the turn in the game state is attackers
     // (because we arbitrarily choose attackers as maximising in the heuristic) and the minimising
     // player if the turn in the game state is the defenders.
-    let player = match game_state.turn() {
-        Player::Attacker => MinMaxPlayer::Maximising,
-        Player::Defender => MinMaxPlayer::Minimising,
-    };
+    let player = MinMaxPlayer::from(game_state.turn());
+    let total = plays.len() as u32;
+    let searched = AtomicU32::new(0);
 
     match player {
         MinMaxPlayer::Maximising => {
             // Attackers want to maximise the heuristic, so it starts at -infinity.
             let dummy = (α, plays[0].clone());
-            let (_best_value, best_play) = plays
+            let (best_value, best_play) = plays
                 .par_iter()
                 .fold(|| dummy.clone(),
                 |(best_value, best_play), play| {
                     let state = {
                         let mut copy = game_state.clone();
                         copy
-                            .make_play(&play)
+                            .make_play(play)
                             .expect("Using available plays should mean making a play never fails");
                         copy
                     };
+                    let next = MinMaxPlayer::from(state.turn());
                     // To avoid serialising the algorithm with a critical section we won't write
                     // to α or β for the top level iteration. Children will still be able to cull
                     // work via α and β optimisations. This will mean we might do more work overall
                     // but it can happen more in parallel.
-                    let value = min_max(state, depth_remaining - 1, α, β, player.next());
+                    let value = min_max(
+                        state,
+                        starting_depth,
+                        depth_remaining - 1,
+                        α,
+                        β,
+                        next,
+                    );
+                    progress(searched.fetch_add(1, Ordering::Relaxed) + 1, total);
                     if value > best_value {
                         (value, play.clone())
                     } else {
@@ -67,27 +100,36 @@ pub fn min_max_play(game_state: &GameState) -> Option<Play> {
                         (best_value, best_play)
                     }
                 });
-            Some(best_play)
+            Some((best_play, best_value.0))
         },
         MinMaxPlayer::Minimising => {
             // Defenders want to minimise the heuristic, so it starts at infinity.
             let dummy = (β, plays[0].clone());
-            let (_best_value, best_play) = plays
+            let (best_value, best_play) = plays
                 .par_iter()
                 .fold(|| dummy.clone(),
                 |(best_value, best_play), play| {
                     let state = {
                         let mut copy = game_state.clone();
                         copy
-                            .make_play(&play)
+                            .make_play(play)
                             .expect("Using available plays should mean making a play never fails");
                         copy
                     };
+                    let next = MinMaxPlayer::from(state.turn());
                     // To avoid serialising the algorithm with a critical section we won't write
                     // to α or β for the top level iteration. Children will still be able to cull
                     // work via α and β optimisations. This will mean we might do more work overall
                     // but it can happen more in parallel.
-                    let value = min_max(state, depth_remaining - 1, α, β, player.next());
+                    let value = min_max(
+                        state,
+                        starting_depth,
+                        depth_remaining - 1,
+                        α,
+                        β,
+                        next,
+                    );
+                    progress(searched.fetch_add(1, Ordering::Relaxed) + 1, total);
                     if value < best_value {
                         (value, play.clone())
                     } else {
@@ -103,7 +145,7 @@ pub fn min_max_play(game_state: &GameState) -> Option<Play> {
                         (best_value, best_play)
                     }
                 });
-            Some(best_play)
+            Some((best_play, best_value.0))
         },
     }
 }
@@ -119,11 +161,13 @@ enum MinMaxPlayer {
     Minimising,
 }
 
-impl MinMaxPlayer {
-    fn next(self) -> MinMaxPlayer {
-        match self {
-            MinMaxPlayer::Maximising => MinMaxPlayer::Minimising,
-            MinMaxPlayer::Minimising => MinMaxPlayer::Maximising,
+impl From<Player> for MinMaxPlayer {
+    /// The player to move is usually the other side, but not always, since a berserk chain of
+    /// captures gives the same side another play.
+    fn from(player: Player) -> Self {
+        match player {
+            Player::Attacker => MinMaxPlayer::Maximising,
+            Player::Defender => MinMaxPlayer::Minimising,
         }
     }
 }
@@ -133,6 +177,7 @@ impl MinMaxPlayer {
 // the turn player for this game state.
 fn min_max(
     game_state: GameState,
+    starting_depth: u8,
     depth_remaining: u8,
     alpha: Heuristic,
     beta: Heuristic,
@@ -143,7 +188,7 @@ fn min_max(
     // because the opponent can't actually deny it one or two turns later.
     // If it can win at maximum depth remaining we want the penalty to be 0 as this is
     // the best possible move the bot could take.
-    let victory_delay_penalty = ((STARTING_DEPTH - 1) - depth_remaining) as i8;
+    let victory_delay_penalty = ((starting_depth - 1) - depth_remaining) as i8;
     if let Some(winner) = game_state.winner() {
         return match winner {
             // Victory for defenders is min score
@@ -152,6 +197,9 @@ fn min_max(
             Player::Attacker => Heuristic(i8::MAX - victory_delay_penalty),
         };
     }
+    if game_state.is_draw() {
+        return Heuristic(0);
+    }
     if depth_remaining == 0 {
         // With low depth it's far too easy for the algorithm to be looking at what it can do
         // then considering any move the player might make (which may for example move the king
@@ -185,6 +233,10 @@ fn min_max(
                 }
             },
             MinMaxPlayer::Maximising => {
+                // With two or more open paths to the corners the attackers can only block one,
+                // so unless they capture the king the defenders win on their next turn
+                let tuichi = threats::king_call(&threats::escape_routes(&game_state))
+                    == Some(KingCall::Tuichi);
                 let possible_king_capture_positions = {
                     let (x, y) = king;
                     let (length_w, length_h) = game_state.size();
@@ -229,14 +281,18 @@ fn min_max(
                         }
                     }
                 }
+                if tuichi {
+                    // Victory for defenders a turn later than if it was their turn
+                    return Heuristic(i8::MIN + victory_delay_penalty + 1);
+                }
             }
         }
         // otherwise approximate value of this state based on number of pieces alive
         // this is very approximate, as there are more attackers than defenders so piece loss
         // might not be of equal value, but need to start with something
         let dead = game_state.dead();
-        let dead_attackers = dead.iter().filter(|&&piece| piece == Piece::Attacker).count();
-        let dead_defenders = dead.iter().filter(|&&piece| piece == Piece::Defender).count();
+        let dead_attackers = dead.iter().filter(|&&piece| matches!(piece, Piece::Attacker | Piece::Commander)).count();
+        let dead_defenders = dead.iter().filter(|&&piece| matches!(piece, Piece::Defender | Piece::Knight)).count();
         return Heuristic((dead_defenders as i8) - (dead_attackers as i8));
     }
     if plays.is_empty() {
@@ -245,7 +301,7 @@ fn min_max(
     // Attackers will be maximising alpha, defenders minimising beta
     let mut α = alpha;
     let mut β = beta;
-    return match player {
+    match player {
         MinMaxPlayer::Maximising => {
             let mut best_value = Heuristic(i8::MIN);
             for play in plays {
@@ -256,9 +312,10 @@ fn min_max(
                         .expect("Using available plays should mean making a play never fails");
                     copy
                 };
+                let next = MinMaxPlayer::from(state.turn());
                 best_value = std::cmp::max(
                     best_value,
-                    min_max(state, depth_remaining - 1, α, β, player.next())
+                    min_max(state, starting_depth, depth_remaining - 1, α, β, next)
                 );
                 // We can guarantee at least this score of alpha by choosing the highest
                 // scoring play available
@@ -279,9 +336,10 @@ fn min_max(
                         .expect("Using available plays should mean making a play never fails");
                     copy
                 };
+                let next = MinMaxPlayer::from(state.turn());
                 best_value = std::cmp::min(
                     best_value,
-                    min_max(state, depth_remaining - 1, α, β, player.next())
+                    min_max(state, starting_depth, depth_remaining - 1, α, β, next)
                 );
                 // We can guarantee at least this score of beta by choosing the lowest
                 // scoring play available
@@ -292,7 +350,7 @@ fn min_max(
             }
             best_value
         }
-    };
+    }
 }
 
 // Although the minmax algorithm is randomised because it will break ties differently on randomised
@@ -322,7 +380,7 @@ fn defenders_minmax_takes_the_winning_move() {
             E, E, E, E, A, A, E, A, E, E, E,
         ])
     };
-    let mut game_state = GameState::from_setup(board, Player::Defender, vec![]);
+    let mut game_state = GameState::from_setup(board, Player::Defender, vec![]).unwrap();
     println!("Game state before defender's turn: {}", game_state);
     let best_play = min_max_play(&game_state).expect("Defenders should have a play to make");
     let result = game_state.make_play(&best_play);
@@ -358,7 +416,7 @@ fn attackers_minmax_takes_the_winning_move() {
             E, E, E, E, A, A, E, A, E, E, E,
         ])
     };
-    let mut game_state = GameState::from_setup(board, Player::Attacker, vec![]);
+    let mut game_state = GameState::from_setup(board, Player::Attacker, vec![]).unwrap();
     println!("Game state before attacker's turn: {}", game_state);
     let best_play = min_max_play(&game_state).expect("Attackers should have a play to make");
     let result = game_state.make_play(&best_play);
@@ -366,3 +424,18 @@ fn attackers_minmax_takes_the_winning_move() {
     assert_eq!(Ok(GameStateUpdate::AttackerWin), result);
     assert_eq!(Some(Player::Attacker), game_state.winner());
 }
+
+#[test]
+fn minmax_reports_progress_for_every_top_level_play() {
+    use std::sync::Mutex;
+    let game_state = GameState::default();
+    let total = game_state.available_plays().len() as u32;
+    let reports = Mutex::new(vec![]);
+    min_max_search_with_progress(&game_state, 1, &|searched, of| {
+        assert_eq!(total, of);
+        reports.lock().unwrap().push(searched);
+    });
+    let mut reports = reports.into_inner().unwrap();
+    reports.sort();
+    assert_eq!((1..=total).collect::<Vec<_>>(), reports);
+}
@@ -1,9 +1,98 @@
 use easy_ml::tensors::Tensor;
+use serde::{Deserialize, Serialize};
 
 use crate::piece::Tile;
-use crate::state::Board;
+use crate::state::{Board, GameState, Play};
+
+use std::fmt::Display;
+use std::str::FromStr;
 
 pub mod minmax;
+pub mod random;
+
+/// The ways a bot can choose which play to make
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    /// Min Max search with alpha beta pruning
+    MinMax,
+    /// Any available play
+    Random,
+}
+
+impl Strategy {
+    /// Picks a play for the turn player, returning None if there are no plays available.
+    pub fn play(&self, game_state: &GameState) -> Option<Play> {
+        match self {
+            Strategy::MinMax => minmax::min_max_play(game_state),
+            Strategy::Random => random::random_play(game_state),
+        }
+    }
+}
+
+/// A bot that plays with a strategy, searching no further ahead than its depth
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Bot {
+    pub strategy: Strategy,
+    /// How many plays ahead min-max searches, which must be at least 1
+    pub depth: u8,
+}
+
+impl Bot {
+    /// Creates a bot that searches as far ahead as min-max does by default
+    pub fn new(strategy: Strategy) -> Self {
+        Bot {
+            strategy,
+            depth: minmax::STARTING_DEPTH,
+        }
+    }
+
+    /// Picks a play for the turn player, returning None if there are no plays available.
+    pub fn play(&self, game_state: &GameState) -> Option<Play> {
+        self.play_with_progress(game_state, &|_, _| ())
+    }
+
+    /// Picks a play like [Bot::play], reporting progress like
+    /// [minmax::min_max_search_with_progress] if searching.
+    pub fn play_with_progress(
+        &self,
+        game_state: &GameState,
+        progress: &(dyn Fn(u32, u32) + Sync),
+    ) -> Option<Play> {
+        match self.strategy {
+            Strategy::MinMax => {
+                minmax::min_max_search_with_progress(game_state, self.depth, progress)
+                    .map(|(play, _)| play)
+            }
+            Strategy::Random => random::random_play(game_state),
+        }
+    }
+}
+
+impl Display for Strategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "{}",
+            match self {
+                Strategy::MinMax => "min-max",
+                Strategy::Random => "random",
+            }
+        )
+    }
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "min-max" => Ok(Strategy::MinMax),
+            "random" => Ok(Strategy::Random),
+            _ => Err(format!("Unknown strategy {}, expected min-max or random", s)),
+        }
+    }
+}
 
 /// Encodes a Board of Tiles into a 3 dimensional Tensor where the third dimension encodes
 /// each type of piece.
@@ -12,14 +101,14 @@ pub fn new_input(board: &Board) -> Tensor<f64, 3> {
 
     let mut attackers = representation.select_mut([("piece", 0)]);
     for ([y, x], value) in attackers.iter_reference_mut().with_index() {
-        if board[(x as u8, y as u8)] == Tile::Attacker {
+        if matches!(board[(x as u8, y as u8)], Tile::Attacker | Tile::Commander) {
             *value = 1.0;
         }
     }
 
     let mut defenders = representation.select_mut([("piece", 1)]);
     for ([y, x], value) in defenders.iter_reference_mut().with_index() {
-        if board[(x as u8, y as u8)] == Tile::Defender {
+        if matches!(board[(x as u8, y as u8)], Tile::Defender | Tile::Knight) {
             *value = 1.0;
         }
     }
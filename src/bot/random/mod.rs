@@ -0,0 +1,10 @@
+use crate::state::{GameState, Play};
+
+use rand::prelude::*;
+
+/// Picks any of the available plays with equal probability, mostly useful as a baseline to
+/// measure other strategies against.
+pub fn random_play(game_state: &GameState) -> Option<Play> {
+    let mut rng = rand::thread_rng();
+    game_state.available_plays().choose(&mut rng).cloned()
+}
@@ -0,0 +1,283 @@
+use crate::state::Player;
+
+use std::fmt::Debug;
+use std::time::{Duration, Instant};
+
+/// A source of monotonically increasing time, so the clock can be driven by something other
+/// than the system time in tests.
+pub trait TimeSource: Debug + Send {
+    /// Returns the time elapsed since some fixed point
+    fn now(&self) -> Duration;
+}
+
+/// Time from the system's monotonic clock
+#[derive(Clone, Debug)]
+pub struct SystemTimeSource {
+    start: Instant,
+}
+
+impl SystemTimeSource {
+    pub fn new() -> Self {
+        SystemTimeSource {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemTimeSource {
+    fn default() -> Self {
+        SystemTimeSource::new()
+    }
+}
+
+impl TimeSource for SystemTimeSource {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TimeControl {
+    /// Each player starts with the initial time and gains the increment after every play.
+    Fischer {
+        initial: Duration,
+        increment: Duration,
+    },
+    /// Each player starts with the initial time, after which they have a number of periods to
+    /// make each play in. A period is only used up if it runs out before the play is made.
+    ByoYomi {
+        initial: Duration,
+        period: Duration,
+        periods: u32,
+    },
+}
+
+/// The time a player has left
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TimeBank {
+    pub main: Duration,
+    /// Byo-yomi periods left, always 0 for Fischer time controls
+    pub periods: u32,
+}
+
+/// A game clock counting down the time of whichever player is making the current turn.
+#[derive(Debug)]
+pub struct Clock {
+    control: TimeControl,
+    attackers: TimeBank,
+    defenders: TimeBank,
+    /// The player whose time is running, and when their turn started
+    running: Option<(Player, Duration)>,
+    /// The player who ran out of time, if any
+    flagged: Option<Player>,
+    source: Box<dyn TimeSource>,
+}
+
+impl Clock {
+    /// Creates a stopped clock with full time banks for each player
+    pub fn new(control: TimeControl, source: Box<dyn TimeSource>) -> Self {
+        let bank = match control {
+            TimeControl::Fischer { initial, .. } => TimeBank {
+                main: initial,
+                periods: 0,
+            },
+            TimeControl::ByoYomi {
+                initial, periods, ..
+            } => TimeBank {
+                main: initial,
+                periods,
+            },
+        };
+        Clock {
+            control,
+            attackers: bank,
+            defenders: bank,
+            running: None,
+            flagged: None,
+            source,
+        }
+    }
+
+    /// Starts counting down the time of the player
+    pub fn start(&mut self, player: Player) {
+        if self.flagged.is_none() {
+            self.running = Some((player, self.source.now()));
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.running = None;
+    }
+
+    /// Returns the player whose time is running, if any
+    pub fn running(&self) -> Option<Player> {
+        self.running.map(|(player, _)| player)
+    }
+
+    /// Returns the time a player has left, including the time used so far on a running turn
+    pub fn remaining(&self, player: Player) -> TimeBank {
+        match self.running {
+            Some((running, started)) if running == player => {
+                self.spend(*self.bank(player), self.source.now() - started)
+            }
+            _ => *self.bank(player),
+        }
+    }
+
+    /// Checks if the player whose time is running has run out, returning them if so.
+    pub fn check(&mut self) -> Option<Player> {
+        if let Some((player, _)) = self.running {
+            let bank = self.remaining(player);
+            if bank.main.is_zero() && bank.periods == 0 {
+                *self.bank_mut(player) = bank;
+                self.flagged = Some(player);
+                self.running = None;
+            }
+        }
+        self.flagged
+    }
+
+    /// Stops the running player's time after they made a play and starts the other player's.
+    ///
+    /// Returns the player who ran out of time if the play was made too late.
+    pub fn press(&mut self) -> Result<(), Player> {
+        if let Some(player) = self.check() {
+            return Err(player);
+        }
+        let (player, started) = match self.running {
+            Some(running) => running,
+            None => return Ok(()),
+        };
+        let mut bank = self.spend(*self.bank(player), self.source.now() - started);
+        match self.control {
+            TimeControl::Fischer { increment, .. } => bank.main += increment,
+            // Making a play within a period means it's not used up
+            TimeControl::ByoYomi { .. } => (),
+        }
+        *self.bank_mut(player) = bank;
+        self.start(player.next());
+        Ok(())
+    }
+
+    /// Takes the elapsed time from a bank, saturating at no time left.
+    fn spend(&self, bank: TimeBank, elapsed: Duration) -> TimeBank {
+        if elapsed < bank.main {
+            return TimeBank {
+                main: bank.main - elapsed,
+                periods: bank.periods,
+            };
+        }
+        let overtime = elapsed - bank.main;
+        match self.control {
+            TimeControl::Fischer { .. } => TimeBank {
+                main: Duration::ZERO,
+                periods: 0,
+            },
+            TimeControl::ByoYomi { period, .. } => {
+                if period.is_zero() {
+                    return TimeBank {
+                        main: Duration::ZERO,
+                        periods: 0,
+                    };
+                }
+                let used = (overtime.as_nanos() / period.as_nanos()) as u32;
+                TimeBank {
+                    main: Duration::ZERO,
+                    periods: bank.periods.saturating_sub(used),
+                }
+            }
+        }
+    }
+
+    fn bank(&self, player: Player) -> &TimeBank {
+        match player {
+            Player::Attacker => &self.attackers,
+            Player::Defender => &self.defenders,
+        }
+    }
+
+    fn bank_mut(&mut self, player: Player) -> &mut TimeBank {
+        match player {
+            Player::Attacker => &mut self.attackers,
+            Player::Defender => &mut self.defenders,
+        }
+    }
+}
+
+#[cfg(test)]
+#[derive(Clone, Debug, Default)]
+struct ManualTimeSource(std::sync::Arc<std::sync::Mutex<Duration>>);
+
+#[cfg(test)]
+impl ManualTimeSource {
+    fn advance(&self, milliseconds: u64) {
+        *self.0.lock().unwrap() += Duration::from_millis(milliseconds);
+    }
+}
+
+#[cfg(test)]
+impl TimeSource for ManualTimeSource {
+    fn now(&self) -> Duration {
+        *self.0.lock().unwrap()
+    }
+}
+
+#[test]
+fn fischer_increment_is_added_after_each_play() {
+    let time = ManualTimeSource::default();
+    let mut clock = Clock::new(
+        TimeControl::Fischer {
+            initial: Duration::from_secs(10),
+            increment: Duration::from_secs(2),
+        },
+        Box::new(time.clone()),
+    );
+    clock.start(Player::Defender);
+    time.advance(3000);
+    assert_eq!(
+        Duration::from_secs(7),
+        clock.remaining(Player::Defender).main
+    );
+    assert_eq!(Ok(()), clock.press());
+    assert_eq!(
+        Duration::from_secs(9),
+        clock.remaining(Player::Defender).main
+    );
+    assert_eq!(Some(Player::Attacker), clock.running());
+    time.advance(12_000);
+    assert_eq!(Some(Player::Attacker), clock.check());
+    assert_eq!(Err(Player::Attacker), clock.press());
+    assert_eq!(Duration::ZERO, clock.remaining(Player::Attacker).main);
+}
+
+#[test]
+fn byo_yomi_periods_are_only_used_when_they_run_out() {
+    let time = ManualTimeSource::default();
+    let mut clock = Clock::new(
+        TimeControl::ByoYomi {
+            initial: Duration::from_secs(5),
+            period: Duration::from_secs(3),
+            periods: 2,
+        },
+        Box::new(time.clone()),
+    );
+    clock.start(Player::Attacker);
+    // Into the first period but made in time
+    time.advance(7000);
+    assert_eq!(Ok(()), clock.press());
+    assert_eq!(
+        TimeBank {
+            main: Duration::ZERO,
+            periods: 2
+        },
+        clock.remaining(Player::Attacker)
+    );
+    assert_eq!(Ok(()), clock.press());
+    // Uses up one period
+    time.advance(4000);
+    assert_eq!(Ok(()), clock.press());
+    assert_eq!(1, clock.remaining(Player::Attacker).periods);
+    assert_eq!(Ok(()), clock.press());
+    time.advance(3000);
+    assert_eq!(Err(Player::Attacker), clock.press());
+}
@@ -11,7 +11,7 @@ impl TryFrom<&str> for Config {
     type Error = toml::de::Error;
 
     fn try_from(toml: &str) -> Result<Config, Self::Error> {
-        toml::from_str(&toml)
+        toml::from_str(toml)
     }
 }
 
@@ -0,0 +1,343 @@
+use crate::piece::{Piece, Tile};
+use crate::rules::Rules;
+use crate::state::{GameState, Player};
+
+use std::fmt::Display;
+
+use easy_ml::matrices::Matrix;
+
+/// The number of squares along each side of the board
+const SIZE: usize = 11;
+const THRONE: Position = (5, 5);
+
+type Position = (u8, u8);
+
+/// A position being set up by hand, which can start a game once it is valid
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BoardEditor {
+    /// The tiles in row major order, which might not fill the board if they were given by the
+    /// caller
+    tiles: Vec<Tile>,
+    turn: Player,
+    dead: Vec<Piece>,
+    rules: Rules,
+}
+
+/// Something that stops an edited position from starting a game
+#[derive(Clone, Copy, Debug, Eq, PartialEq, uniffi::Enum)]
+pub enum SetupProblem {
+    /// The tiles don't fill the board exactly
+    SizeMismatch {
+        expected: u32,
+        actual: u32,
+    },
+    NoKing,
+    /// There can only be one king on the board
+    TooManyKings {
+        kings: u32,
+    },
+    /// The king would have already escaped
+    KingOnCorner {
+        x: u8,
+        y: u8,
+    },
+    /// Only the king can stand on the corners
+    PieceOnCorner {
+        piece: Tile,
+        x: u8,
+        y: u8,
+    },
+    /// Only the king can stand on the throne
+    PieceOnThrone {
+        piece: Tile,
+    },
+    /// Commanders and knights are only part of the berserk rules
+    BerserkPiece {
+        piece: Tile,
+    },
+}
+
+/// Why an edit couldn't be made
+#[derive(Clone, Debug, Eq, PartialEq, uniffi::Enum)]
+pub enum EditError {
+    /// The position is not on the board
+    OffBoard { x: u8, y: u8 },
+    /// The position can't start a game, or be edited square by square, because of the
+    /// problems
+    Invalid(Vec<SetupProblem>),
+}
+
+impl BoardEditor {
+    /// Creates an editor with an empty board, with the first player of the rules to move
+    pub fn new(rules: Rules) -> Self {
+        BoardEditor {
+            tiles: vec![Tile::Empty; SIZE * SIZE],
+            turn: rules.first_player,
+            dead: vec![],
+            rules,
+        }
+    }
+
+    /// Creates an editor with the tiles in row major order. If there aren't enough or are too
+    /// many the position won't be valid until they are set again.
+    pub fn with_tiles(rules: Rules, tiles: Vec<Tile>) -> Self {
+        BoardEditor {
+            tiles,
+            ..BoardEditor::new(rules)
+        }
+    }
+
+    /// Creates an editor with the current position of the game
+    pub fn from_state(game_state: &GameState) -> Self {
+        BoardEditor {
+            tiles: game_state.pieces().row_major_iter().collect(),
+            turn: game_state.turn(),
+            dead: game_state.dead().to_vec(),
+            rules: game_state.rules(),
+        }
+    }
+
+    /// Replaces every tile, in row major order
+    pub fn set_tiles(&mut self, tiles: Vec<Tile>) {
+        self.tiles = tiles;
+    }
+
+    /// Puts the tile on the square, replacing whatever was there. The tiles must fill the
+    /// board exactly, so tiles given by the caller that don't are left as they are until
+    /// they are set again.
+    pub fn place(&mut self, position: Position, tile: Tile) -> Result<(), EditError> {
+        let index = self.index(position)?;
+        if let Some(problem) = self.size_mismatch() {
+            return Err(EditError::Invalid(vec![problem]));
+        }
+        self.tiles[index] = tile;
+        Ok(())
+    }
+
+    /// Empties the square
+    pub fn remove(&mut self, position: Position) -> Result<(), EditError> {
+        self.place(position, Tile::Empty)
+    }
+
+    /// Returns the tile on the square, which is empty if it hasn't been set yet
+    pub fn tile(&self, position: Position) -> Result<Tile, EditError> {
+        let index = self.index(position)?;
+        Ok(self.tiles.get(index).copied().unwrap_or(Tile::Empty))
+    }
+
+    pub fn set_turn(&mut self, turn: Player) {
+        self.turn = turn;
+    }
+
+    pub fn set_dead(&mut self, dead: Vec<Piece>) {
+        self.dead = dead;
+    }
+
+    pub fn set_rules(&mut self, rules: Rules) {
+        self.rules = rules;
+    }
+
+    /// Returns every problem with the position, which is empty if it can start a game
+    pub fn validate(&self) -> Vec<SetupProblem> {
+        let mut problems = Vec::new();
+        problems.extend(self.size_mismatch());
+        let kings: Vec<Position> = self
+            .squares()
+            .filter(|&(_, tile)| tile == Tile::King)
+            .map(|(position, _)| position)
+            .collect();
+        match kings.len() {
+            0 => problems.push(SetupProblem::NoKing),
+            1 => (),
+            kings => problems.push(SetupProblem::TooManyKings {
+                kings: kings as u32,
+            }),
+        }
+        let edges = [0, SIZE as u8 - 1];
+        for ((x, y), tile) in self.squares() {
+            if !edges.contains(&x) || !edges.contains(&y) {
+                continue;
+            }
+            match tile {
+                Tile::Empty => (),
+                Tile::King => problems.push(SetupProblem::KingOnCorner { x, y }),
+                piece => problems.push(SetupProblem::PieceOnCorner { piece, x, y }),
+            }
+        }
+        match self.tile(THRONE) {
+            Ok(Tile::Empty | Tile::King) | Err(_) => (),
+            Ok(piece) => problems.push(SetupProblem::PieceOnThrone { piece }),
+        }
+        if !self.rules.berserk {
+            for piece in [Tile::Commander, Tile::Knight] {
+                if self.squares().any(|(_, tile)| tile == piece) {
+                    problems.push(SetupProblem::BerserkPiece { piece });
+                }
+            }
+        }
+        problems
+    }
+
+    /// Starts a game from the position, or returns every problem stopping it if it isn't valid
+    pub fn start(&self) -> Result<GameState, EditError> {
+        let problems = self.validate();
+        if !problems.is_empty() {
+            return Err(EditError::Invalid(problems));
+        }
+        GameState::from_setup(
+            Matrix::from_flat_row_major((SIZE, SIZE), self.tiles.clone()),
+            self.turn,
+            self.dead.clone(),
+        )
+        .map(|game_state| game_state.with_rules(self.rules))
+        .map_err(|problem| EditError::Invalid(vec![problem]))
+    }
+
+    /// Returns the index of the tile at the position, if it is on the board
+    fn index(&self, (x, y): Position) -> Result<usize, EditError> {
+        let (column, row) = (x as usize, y as usize);
+        if column < SIZE && row < SIZE {
+            Ok(column + row * SIZE)
+        } else {
+            Err(EditError::OffBoard { x, y })
+        }
+    }
+
+    /// Returns the problem with the number of tiles, if they don't fill the board exactly
+    fn size_mismatch(&self) -> Option<SetupProblem> {
+        (self.tiles.len() != SIZE * SIZE).then_some(SetupProblem::SizeMismatch {
+            expected: (SIZE * SIZE) as u32,
+            actual: self.tiles.len() as u32,
+        })
+    }
+
+    /// Returns the position of each tile that is on the board
+    fn squares(&self) -> impl Iterator<Item = (Position, Tile)> + '_ {
+        self.tiles
+            .iter()
+            .take(SIZE * SIZE)
+            .enumerate()
+            .map(|(i, &tile)| (((i % SIZE) as u8, (i / SIZE) as u8), tile))
+    }
+}
+
+impl Display for SetupProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            SetupProblem::SizeMismatch { expected, actual } => write!(
+                f,
+                "The board needs {} tiles but {} were given",
+                expected, actual
+            ),
+            SetupProblem::NoKing => write!(f, "There is no king on the board"),
+            SetupProblem::TooManyKings { kings } => {
+                write!(
+                    f,
+                    "There are {} kings on the board but only 1 is allowed",
+                    kings
+                )
+            }
+            SetupProblem::KingOnCorner { x, y } => write!(
+                f,
+                "The king is on the corner at {:?} so would have already escaped",
+                (x, y)
+            ),
+            SetupProblem::PieceOnCorner { piece, x, y } => write!(
+                f,
+                "Only the king can be on the corners, not {} at {:?}",
+                piece,
+                (x, y)
+            ),
+            SetupProblem::PieceOnThrone { piece } => {
+                write!(f, "Only the king can be on the throne, not {}", piece)
+            }
+            SetupProblem::BerserkPiece { piece } => {
+                write!(f, "There can only be a {:?} with the berserk rules", piece)
+            }
+        }
+    }
+}
+
+impl Display for EditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            EditError::OffBoard { x, y } => write!(f, "{:?} is not on the board", (x, y)),
+            EditError::Invalid(problems) => write!(
+                f,
+                "The position can't start a game because of {} problems",
+                problems.len()
+            ),
+        }
+    }
+}
+
+#[test]
+fn editor_reports_every_problem_with_the_position() {
+    let mut editor = BoardEditor::with_tiles(Rules::default(), vec![Tile::Empty; 100]);
+    assert_eq!(
+        vec![
+            SetupProblem::SizeMismatch {
+                expected: 121,
+                actual: 100,
+            },
+            SetupProblem::NoKing,
+        ],
+        editor.validate()
+    );
+    assert_eq!(
+        Err(EditError::Invalid(vec![SetupProblem::SizeMismatch {
+            expected: 121,
+            actual: 100,
+        }])),
+        editor.place((3, 3), Tile::King)
+    );
+    assert_eq!(Ok(Tile::Empty), editor.tile((3, 3)));
+    editor.set_tiles(vec![Tile::Empty; 121]);
+    editor.place((5, 5), Tile::Attacker).unwrap();
+    editor.place((10, 0), Tile::King).unwrap();
+    editor.place((3, 3), Tile::King).unwrap();
+    editor.place((0, 10), Tile::Defender).unwrap();
+    editor.place((7, 2), Tile::Knight).unwrap();
+    assert_eq!(
+        Err(EditError::OffBoard { x: 11, y: 0 }),
+        editor.place((11, 0), Tile::King)
+    );
+    assert_eq!(
+        vec![
+            SetupProblem::TooManyKings { kings: 2 },
+            SetupProblem::KingOnCorner { x: 10, y: 0 },
+            SetupProblem::PieceOnCorner {
+                piece: Tile::Defender,
+                x: 0,
+                y: 10,
+            },
+            SetupProblem::PieceOnThrone {
+                piece: Tile::Attacker,
+            },
+            SetupProblem::BerserkPiece {
+                piece: Tile::Knight
+            },
+        ],
+        editor.validate()
+    );
+    assert!(editor.start().is_err());
+    editor.set_rules(Rules::berserk());
+    assert!(!editor.validate().contains(&SetupProblem::BerserkPiece {
+        piece: Tile::Knight
+    }));
+    editor.set_rules(Rules::default());
+
+    editor.remove((5, 5)).unwrap();
+    editor.remove((10, 0)).unwrap();
+    editor.remove((0, 10)).unwrap();
+    editor.remove((7, 2)).unwrap();
+    editor.place((4, 3), Tile::Attacker).unwrap();
+    editor.set_turn(Player::Attacker);
+    editor.set_dead(vec![Piece::Defender]);
+    assert!(editor.validate().is_empty());
+    let game_state = editor.start().unwrap();
+    assert_eq!((3, 3), game_state.king_position());
+    assert_eq!(Player::Attacker, game_state.turn());
+    assert_eq!(&vec![Piece::Defender], game_state.dead());
+    assert_eq!(editor, BoardEditor::from_state(&game_state));
+}
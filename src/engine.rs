@@ -0,0 +1,587 @@
+use crate::bot::minmax::{min_max_search, STARTING_DEPTH};
+use crate::piece::Tile;
+use crate::rules::{KingCapture, Repetition, Rules};
+use crate::state::{GameState, Play, Player};
+
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use easy_ml::matrices::Matrix;
+
+/// An engine speaking the OpenTafl engine protocol, reading commands from stdin one per line
+/// and writing responses to stdout.
+///
+/// Supported commands:
+/// - `hello`, answered with `hello`
+/// - `rules <rules>`, an OpenTafl rules record, which is refused if it asks for a variant we
+///   can't play (see `parse_rules`)
+/// - `position <position>`, replacing the pieces on the board
+/// - `side <attackers|defenders>`, the side the engine is playing
+/// - `clock <attackers>ms <defenders>ms <overtime>s <attacker overtimes> <defender overtimes>
+///   [<increment>s][*]`, the time each side has remaining and the byo-yomi periods they have
+///   left
+/// - `opponent-move <move> [position]`, a play made by the opponent and the position it led to
+/// - `play <attackers|defenders>`, answered with `move <move>`
+/// - `analyze <moves> <seconds>`, answered with `analysis <rating> <move>`
+/// - `goodbye`, which stops the engine
+///
+/// Anything else is ignored, and problems are reported on stderr so they can't be confused
+/// with responses.
+pub struct Engine {
+    game_state: GameState,
+    side: Player,
+    clock: Option<Clock>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Clock {
+    attackers: Duration,
+    defenders: Duration,
+    /// The time for each play once a side's main time has run out
+    overtime: Duration,
+    attacker_overtimes: u32,
+    defender_overtimes: u32,
+    increment: Duration,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Engine {
+            game_state: GameState::default(),
+            side: Player::Attacker,
+            clock: None,
+        }
+    }
+
+    /// Handles commands until stdin is closed or a `goodbye` is received.
+    pub fn run(&mut self) -> io::Result<()> {
+        let stdin = io::stdin();
+        let mut stdout = io::stdout();
+        for line in stdin.lock().lines() {
+            let line = line?;
+            match self.handle(line.trim()) {
+                Ok(Some(response)) => {
+                    writeln!(stdout, "{}", response)?;
+                    stdout.flush()?;
+                }
+                Ok(None) => (),
+                Err(EngineError::Goodbye) => return Ok(()),
+                Err(EngineError::Invalid(error)) => eprintln!("{}", error),
+            }
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, line: &str) -> Result<Option<String>, EngineError> {
+        let (command, arguments) = line.split_once(' ').unwrap_or((line, ""));
+        let arguments = arguments.trim();
+        match command {
+            "hello" => Ok(Some("hello".to_string())),
+            "goodbye" => Err(EngineError::Goodbye),
+            "rules" => self.rules(arguments).map(|_| None),
+            "position" => {
+                let pieces = parse_position(arguments)?;
+                let turn = self.game_state.turn();
+                self.game_state.set_position(pieces, turn);
+                Ok(None)
+            }
+            "side" => {
+                self.side = parse_side(arguments)?;
+                Ok(None)
+            }
+            "clock" => {
+                self.clock = Some(parse_clock(arguments)?);
+                Ok(None)
+            }
+            "opponent-move" => {
+                let (play, position) = arguments.split_once(' ').unwrap_or((arguments, ""));
+                let play = parse_move(play, self.game_state.size())?;
+                self.game_state.make_play(&play).map_err(|reason| {
+                    EngineError::Invalid(format!("Illegal move {}: {}", arguments, reason))
+                })?;
+                // The controller's position is the authority if we worked out anything else
+                let position = position.trim();
+                if !position.is_empty() {
+                    let pieces = parse_position(position)?;
+                    if pieces != self.game_state.pieces() {
+                        let turn = self.game_state.turn();
+                        self.game_state.set_position(pieces, turn);
+                    }
+                }
+                Ok(None)
+            }
+            "play" => {
+                self.side = parse_side(arguments)?;
+                self.set_turn(self.side);
+                let (play, _) = self.search(self.depth())?;
+                self.game_state
+                    .make_play(&play)
+                    .expect("Using available plays should mean making a play never fails");
+                Ok(Some(format!(
+                    "move {}",
+                    format_move(&play, self.game_state.size())
+                )))
+            }
+            "analyze" => {
+                let seconds: u64 = arguments
+                    .split_whitespace()
+                    .nth(1)
+                    .and_then(|seconds| seconds.parse().ok())
+                    .unwrap_or(0);
+                let depth = depth_for(Duration::from_secs(seconds), Duration::ZERO);
+                let (play, rating) = self.search(depth)?;
+                Ok(Some(format!(
+                    "analysis {} {}",
+                    rating,
+                    format_move(&play, self.game_state.size())
+                )))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn rules(&mut self, arguments: &str) -> Result<(), EngineError> {
+        let (rules, start) = parse_rules(arguments)?;
+        let pieces = match start {
+            Some(pieces) => pieces,
+            None if rules.berserk => GameState::berserk().pieces(),
+            None => GameState::default().pieces(),
+        };
+        self.game_state = GameState::from_setup(pieces, rules.first_player, vec![])
+            .map_err(|problem| EngineError::Invalid(format!("Invalid start: {}", problem)))?
+            .with_rules(rules);
+        Ok(())
+    }
+
+    /// Position updates don't say whose turn it is, so we take it from what we're asked to do
+    fn set_turn(&mut self, turn: Player) {
+        let pieces = self.game_state.pieces();
+        self.game_state.set_position(pieces, turn);
+    }
+
+    /// How deep to search on our turn, given how much time we have left.
+    fn depth(&self) -> u8 {
+        match self.clock {
+            Some(clock) => {
+                let (remaining, overtimes) = match self.side {
+                    Player::Attacker => (clock.attackers, clock.attacker_overtimes),
+                    Player::Defender => (clock.defenders, clock.defender_overtimes),
+                };
+                let budget = match overtimes {
+                    0 => remaining / 30,
+                    _ => (remaining / 30).max(clock.overtime),
+                };
+                depth_for(budget, clock.increment)
+            }
+            None => STARTING_DEPTH,
+        }
+    }
+
+    fn search(&self, depth: u8) -> Result<(Play, i8), EngineError> {
+        min_max_search(&self.game_state, depth)
+            .ok_or_else(|| EngineError::Invalid("No play is available to make".to_string()))
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::new()
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum EngineError {
+    Goodbye,
+    Invalid(String),
+}
+
+/// Picks a search depth that should take no longer than the time we want to spend on a play.
+///
+/// These thresholds are rough guesses from how long each depth takes on a starting position
+/// in a release build.
+fn depth_for(budget: Duration, increment: Duration) -> u8 {
+    let budget = budget + increment;
+    if budget < Duration::from_millis(200) {
+        1
+    } else if budget < Duration::from_secs(2) {
+        2
+    } else {
+        STARTING_DEPTH
+    }
+}
+
+fn parse_side(side: &str) -> Result<Player, EngineError> {
+    match side {
+        "attackers" => Ok(Player::Attacker),
+        "defenders" => Ok(Player::Defender),
+        _ => Err(EngineError::Invalid(format!("Unknown side {}", side))),
+    }
+}
+
+/// Parses an OpenTafl rules record, such as `dim:11 atkf:y ks:w start:/.../`, into the rules
+/// and the starting position if one is given. Rules the record doesn't mention keep the
+/// defaults of this game, and a rule with a value we can't play refuses the whole record.
+///
+/// Understood keys:
+/// - `dim`, which must be 11
+/// - `name`, which is ignored
+/// - `esc`, which must be `c` as the king escapes to the corners
+/// - `ka`, which must be `y` as the king always helps capture
+/// - `atkf`, `y` if the attackers move first
+/// - `efe`, `y` if the king wins in an edge fort
+/// - `ber`, `y` for Berserk Hnefatafl
+/// - `sw`, `n` for no shieldwalls or `s` for shieldwalls that the corners can bracket
+/// - `ks`, `s` for a king captured on four sides or `w` for a king captured on two
+/// - `edh`, the pieces the board edge is hostile to, which is empty or `K` to capture a strong
+///   king against the edge
+/// - `cenhe`, the pieces the empty throne is hostile to, from `t` and `T` together and `K`
+/// - `tfr`, `n` to allow repeated positions or `d` to draw on the third
+/// - `start`, the starting position
+fn parse_rules(record: &str) -> Result<(Rules, Option<Matrix<Tile>>), EngineError> {
+    let unsupported = |rule: &str| EngineError::Invalid(format!("Unsupported rule {}", rule));
+    let mut rules = Rules::default();
+    let mut start = None;
+    let mut strong_king = rules.king_capture != KingCapture::Weak;
+    let mut hostile_edge = rules.king_capture == KingCapture::Surrounded;
+    for rule in record.split_whitespace() {
+        let (key, value) = rule.split_once(':').ok_or_else(|| unsupported(rule))?;
+        let flag = || match value {
+            "y" => Ok(true),
+            "n" => Ok(false),
+            _ => Err(unsupported(rule)),
+        };
+        match (key, value) {
+            ("dim", "11") | ("name", _) | ("esc", "c") | ("ka", "y") => (),
+            ("atkf", _) => {
+                rules.first_player = match flag()? {
+                    true => Player::Attacker,
+                    false => Player::Defender,
+                }
+            }
+            ("efe", _) => rules.edge_fort = flag()?,
+            ("ber", _) => rules.berserk = flag()?,
+            ("sw", "n") => rules.shieldwall = false,
+            ("sw", "s") => rules.shieldwall = true,
+            ("ks", "s") => strong_king = true,
+            ("ks", "w") => strong_king = false,
+            ("edh", "") => hostile_edge = false,
+            ("edh", "K") => hostile_edge = true,
+            ("cenhe", pieces) => {
+                let attackers = pieces.contains('t');
+                if attackers != pieces.contains('T') || pieces.contains(|p| !"tTK".contains(p)) {
+                    return Err(unsupported(rule));
+                }
+                rules.hostile_throne = attackers;
+                rules.throne_adjacent_king_capture = pieces.contains('K');
+            }
+            ("tfr", "n") => rules.repetition = Repetition::Allowed,
+            ("tfr", "d") => rules.repetition = Repetition::Draw,
+            ("start", position) => start = Some(parse_position(position)?),
+            _ => return Err(unsupported(rule)),
+        }
+    }
+    rules.king_capture = match (strong_king, hostile_edge) {
+        (false, _) => KingCapture::Weak,
+        (true, true) => KingCapture::Surrounded,
+        (true, false) => KingCapture::EdgeSafe,
+    };
+    Ok((rules, start))
+}
+
+/// Parses an OpenTafl clock update such as `300000ms 300000ms 30s 3 3 0s*`, which is the main
+/// time left for each side, the length of a byo-yomi period, the periods each side has left
+/// and optionally the increment, with a trailing `*` that we have no use for.
+fn parse_clock(clock: &str) -> Result<Clock, EngineError> {
+    let invalid = || EngineError::Invalid(format!("Invalid clock {}", clock));
+    let fields: Vec<&str> = clock.trim_end_matches('*').split_whitespace().collect();
+    let [attackers, defenders, overtime, attacker_overtimes, defender_overtimes, ref increment @ ..] =
+        fields[..]
+    else {
+        return Err(invalid());
+    };
+    let increment = match increment {
+        [] => Duration::ZERO,
+        [increment] => parse_time(increment).ok_or_else(invalid)?,
+        _ => return Err(invalid()),
+    };
+    Ok(Clock {
+        attackers: parse_time(attackers).ok_or_else(invalid)?,
+        defenders: parse_time(defenders).ok_or_else(invalid)?,
+        overtime: parse_time(overtime).ok_or_else(invalid)?,
+        attacker_overtimes: attacker_overtimes.parse().map_err(|_| invalid())?,
+        defender_overtimes: defender_overtimes.parse().map_err(|_| invalid())?,
+        increment,
+    })
+}
+
+/// Parses a time with its unit, such as `300000ms` or `30s`
+fn parse_time(time: &str) -> Option<Duration> {
+    if let Some(millis) = time.strip_suffix("ms") {
+        millis.parse().ok().map(Duration::from_millis)
+    } else {
+        let seconds = time.strip_suffix('s')?;
+        seconds.parse().ok().map(Duration::from_secs)
+    }
+}
+
+/// Parses an OpenTafl position record, such as `/3ttttt3/5t5/11/.../`, where each row from the
+/// top is separated by a `/`, numbers are runs of empty tiles, `t` is an attacker, `T` is a
+/// defender, `K` is the king, `c` is a commander and `N` is a knight.
+fn parse_position(position: &str) -> Result<Matrix<Tile>, EngineError> {
+    let invalid = || EngineError::Invalid(format!("Invalid position {}", position));
+    let rows: Vec<&str> = position
+        .trim_matches('/')
+        .split('/')
+        .filter(|row| !row.is_empty())
+        .collect();
+    let size = rows.len();
+    let mut tiles = Vec::with_capacity(size * size);
+    for row in rows {
+        let mut run = 0;
+        let mut length = 0;
+        for character in row.chars() {
+            if let Some(digit) = character.to_digit(10) {
+                run = run * 10 + digit as usize;
+                continue;
+            }
+            tiles.extend(std::iter::repeat_n(Tile::Empty, run));
+            length += run;
+            run = 0;
+            tiles.push(match character {
+                't' => Tile::Attacker,
+                'T' => Tile::Defender,
+                'K' => Tile::King,
+                'c' => Tile::Commander,
+                'N' => Tile::Knight,
+                _ => return Err(invalid()),
+            });
+            length += 1;
+        }
+        tiles.extend(std::iter::repeat_n(Tile::Empty, run));
+        length += run;
+        if length != size {
+            return Err(invalid());
+        }
+    }
+    if size != 11 || tiles.iter().filter(|&&tile| tile == Tile::King).count() != 1 {
+        return Err(invalid());
+    }
+    Ok(Matrix::from_flat_row_major((size, size), tiles))
+}
+
+/// Parses an OpenTafl move such as `a4-a7`, ignoring any leading piece letter and any trailing
+/// list of captures, which we work out for ourselves.
+fn parse_move(play: &str, size: (u8, u8)) -> Result<Play, EngineError> {
+    let invalid = || EngineError::Invalid(format!("Invalid move {}", play));
+    let trimmed = play.trim_start_matches(|c: char| c.is_ascii_uppercase());
+    let trimmed = trimmed.split('x').next().ok_or_else(invalid)?;
+    let (from, to) = trimmed.split_once('-').ok_or_else(invalid)?;
+    Ok(Play {
+        from: parse_coordinate(from, size).ok_or_else(invalid)?,
+        to: parse_coordinate(to, size).ok_or_else(invalid)?,
+    })
+}
+
+/// Files are letters from the left and ranks are numbers from the bottom, starting at 1.
+fn parse_coordinate(coordinate: &str, size: (u8, u8)) -> Option<(u8, u8)> {
+    let mut characters = coordinate.chars();
+    let file = characters.next()?;
+    if !file.is_ascii_lowercase() {
+        return None;
+    }
+    let x = file as u8 - b'a';
+    let rank: u8 = characters.as_str().parse().ok()?;
+    if x >= size.0 || rank == 0 || rank > size.1 {
+        return None;
+    }
+    Some((x, size.1 - rank))
+}
+
+fn format_move(play: &Play, size: (u8, u8)) -> String {
+    let coordinate = |(x, y): (u8, u8)| format!("{}{}", (b'a' + x) as char, size.1 - y);
+    format!("{}-{}", coordinate(play.from), coordinate(play.to))
+}
+
+#[test]
+fn parses_the_starting_position() {
+    let pieces =
+        parse_position("/3ttttt3/5t5/11/t4T4t/t3TTT3t/tt1TTKTT1tt/t3TTT3t/t4T4t/11/5t5/3ttttt3/")
+            .unwrap();
+    assert_eq!(GameState::default().pieces(), pieces);
+    assert!(parse_position("/3ttttt3/5t5/").is_err());
+}
+
+#[test]
+fn moves_convert_to_and_from_notation() {
+    let size = (11, 11);
+    let play = parse_move("Kf6-f9xf10", size).unwrap();
+    assert_eq!(
+        Play {
+            from: (5, 5),
+            to: (5, 2)
+        },
+        play
+    );
+    assert_eq!("f6-f9", format_move(&play, size));
+    assert_eq!(
+        Play {
+            from: (0, 10),
+            to: (0, 7)
+        },
+        parse_move("a1-a4", size).unwrap()
+    );
+    assert!(parse_move("a0-a4", size).is_err());
+    assert!(parse_move("l1-l4", size).is_err());
+}
+
+#[test]
+fn engine_answers_commands() {
+    let mut engine = Engine::new();
+    assert_eq!(Ok(Some("hello".to_string())), engine.handle("hello"));
+    assert_eq!(Ok(None), engine.handle("rules dim:11 atkf:y"));
+    assert_eq!(Player::Attacker, engine.game_state.turn());
+    assert_eq!(Ok(None), engine.handle("clock 100ms 100ms 0s 0 0"));
+    assert_eq!(Ok(None), engine.handle("opponent-move d11-d9"));
+    assert_eq!(Player::Defender, engine.game_state.turn());
+    let response = engine.handle("play defenders").unwrap().unwrap();
+    assert!(response.starts_with("move "));
+    assert_eq!(Player::Attacker, engine.game_state.turn());
+    assert_eq!(Err(EngineError::Goodbye), engine.handle("goodbye"));
+}
+
+#[test]
+fn position_updates_keep_the_game_going() {
+    use crate::piece::Piece;
+    let mut engine = Engine::new();
+    assert_eq!(Ok(None), engine.handle("rules dim:11 atkf:y"));
+    assert_eq!(Ok(None), engine.handle("opponent-move d11-d9"));
+    // The attacker that moved has since been captured
+    assert_eq!(
+        Ok(None),
+        engine.handle(
+            "position /4tttt3/5t5/11/t4T4t/t3TTT3t/tt1TTKTT1tt/t3TTT3t/t4T4t/11/5t5/3ttttt3/"
+        )
+    );
+    assert_eq!(2, engine.game_state.turn_count());
+    assert_eq!(&vec![Piece::Attacker], engine.game_state.dead());
+    engine.set_turn(Player::Attacker);
+    assert_eq!(2, engine.game_state.turn_count());
+}
+
+#[test]
+fn clock_updates_are_read_with_their_units() {
+    assert_eq!(
+        Ok(Clock {
+            attackers: Duration::from_secs(300),
+            defenders: Duration::from_millis(299_500),
+            overtime: Duration::from_secs(30),
+            attacker_overtimes: 3,
+            defender_overtimes: 2,
+            increment: Duration::ZERO,
+        }),
+        parse_clock("300000ms 299500ms 30s 3 2 0s*")
+    );
+    assert_eq!(
+        Ok(Duration::from_secs(5)),
+        parse_clock("1000ms 1000ms 0s 0 0 5s").map(|clock| clock.increment)
+    );
+    assert!(parse_clock("300000 300000").is_err());
+}
+
+#[test]
+fn engine_follows_an_opentafl_transcript() {
+    let transcript = [
+        ("hello", Some("hello")),
+        (
+            "rules dim:11 name:Test esc:c atkf:y efe:y sw:s ks:s edh: cenhe:tTK tfr:d start:/3ttttt3/5t5/11/t4T4t/t3TTT3t/tt1TTKTT1tt/t3TTT3t/t4T4t/11/5t5/3ttttt3/",
+            None,
+        ),
+        ("side defenders", None),
+        ("clock 3000ms 3000ms 0s 0 0 0s*", None),
+        (
+            "opponent-move d11-d9 /4tttt3/5t5/3t7/t4T4t/t3TTT3t/tt1TTKTT1tt/t3TTT3t/t4T4t/11/5t5/3ttttt3/",
+            None,
+        ),
+    ];
+    let mut engine = Engine::new();
+    for (command, response) in transcript {
+        assert_eq!(
+            Ok(response.map(|response| response.to_string())),
+            engine.handle(command),
+            "{}",
+            command
+        );
+    }
+    assert_eq!(
+        Some(Clock {
+            attackers: Duration::from_secs(3),
+            defenders: Duration::from_secs(3),
+            overtime: Duration::ZERO,
+            attacker_overtimes: 0,
+            defender_overtimes: 0,
+            increment: Duration::ZERO,
+        }),
+        engine.clock
+    );
+    assert_eq!(
+        Rules {
+            shieldwall: true,
+            edge_fort: true,
+            hostile_throne: true,
+            throne_adjacent_king_capture: true,
+            king_capture: KingCapture::EdgeSafe,
+            first_player: Player::Attacker,
+            repetition: Repetition::Draw,
+            ..Rules::default()
+        },
+        engine.game_state.rules()
+    );
+    assert_eq!(
+        Rules {
+            shieldwall: true,
+            edge_fort: true,
+            hostile_throne: true,
+            throne_adjacent_king_capture: true,
+            king_capture: KingCapture::EdgeSafe,
+            first_player: Player::Attacker,
+            repetition: Repetition::Draw,
+            ..Rules::default()
+        },
+        engine.game_state.rules()
+    );
+    assert_eq!(Player::Defender, engine.game_state.turn());
+    let response = engine.handle("play defenders").unwrap().unwrap();
+    assert!(response.starts_with("move "));
+    assert_eq!(Player::Attacker, engine.game_state.turn());
+    assert_eq!(Ok(None), engine.handle("finish 0"));
+    assert_eq!(Err(EngineError::Goodbye), engine.handle("goodbye"));
+}
+
+#[test]
+fn rules_records_we_cant_play_are_refused() {
+    let (rules, start) = parse_rules("dim:11 atkf:n ks:w ber:y").unwrap();
+    assert_eq!(
+        Rules {
+            king_capture: KingCapture::Weak,
+            berserk: true,
+            ..Rules::default()
+        },
+        rules
+    );
+    assert_eq!(None, start);
+    for record in [
+        "dim:13",
+        "esc:e",
+        "sw:w",
+        "tfr:w",
+        "cenhe:t",
+        "ka:n",
+        "atkf:maybe",
+        "kj:y",
+    ] {
+        assert!(parse_rules(record).is_err(), "{}", record);
+    }
+    let mut engine = Engine::new();
+    assert!(engine.handle("rules dim:11 esc:e").is_err());
+    assert_eq!(Rules::default(), engine.game_state.rules());
+}
@@ -1,5 +1,4 @@
 use crate::config::Config;
-use crate::uniffi;
 
 use std::convert::{TryFrom, TryInto};
 use std::fmt;
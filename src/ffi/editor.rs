@@ -0,0 +1,109 @@
+use crate::editor::{BoardEditor, EditError, SetupProblem};
+use crate::ffi::{Dead, GameStateHandle, Ruleset, TurnPlayer};
+use crate::piece::Tile;
+
+use std::sync::{Arc, Mutex};
+
+/// A handle to a position being set up by hand, behind a mutex to allow calling from Kotlin
+/// without issue. Unlike `GameStateHandle::with_starting_configuration` nothing is adjusted
+/// silently, the problems with the position are reported instead.
+#[derive(Debug, uniffi::Object)]
+pub struct BoardEditorHandle {
+    editor: Mutex<BoardEditor>,
+}
+
+#[uniffi::export]
+impl BoardEditorHandle {
+    /// Creates an editor with an empty 11x11 board, played with the ruleset
+    #[uniffi::constructor]
+    fn new(ruleset: Ruleset) -> Self {
+        BoardEditorHandle {
+            editor: Mutex::new(BoardEditor::new(ruleset.into())),
+        }
+    }
+
+    /// Creates an editor with the current position of the game
+    #[uniffi::constructor]
+    fn from_game(game: Arc<GameStateHandle>) -> Self {
+        let game_state = game
+            .state
+            .lock()
+            .expect("Poisoned mutex in BoardEditorHandle from_game");
+        BoardEditorHandle {
+            editor: Mutex::new(BoardEditor::from_state(&game_state)),
+        }
+    }
+
+    /// Replaces every tile with the tiles for the 11x11 board in row major order
+    fn set_tiles(&self, tiles: Vec<Tile>) {
+        self.editor
+            .lock()
+            .expect("Poisoned mutex in BoardEditorHandle set_tiles")
+            .set_tiles(tiles);
+    }
+
+    /// Puts the tile on the square, replacing whatever was there, which fails if the tiles
+    /// given don't fill the board exactly
+    fn place(&self, x: u8, y: u8, tile: Tile) -> Result<(), EditError> {
+        self.editor
+            .lock()
+            .expect("Poisoned mutex in BoardEditorHandle place")
+            .place((x, y), tile)
+    }
+
+    /// Empties the square
+    fn remove(&self, x: u8, y: u8) -> Result<(), EditError> {
+        self.editor
+            .lock()
+            .expect("Poisoned mutex in BoardEditorHandle remove")
+            .remove((x, y))
+    }
+
+    /// Returns the tile on the square
+    fn tile(&self, x: u8, y: u8) -> Result<Tile, EditError> {
+        self.editor
+            .lock()
+            .expect("Poisoned mutex in BoardEditorHandle tile")
+            .tile((x, y))
+    }
+
+    /// Sets the side that makes the first play from the position
+    fn set_turn(&self, turn: TurnPlayer) {
+        self.editor
+            .lock()
+            .expect("Poisoned mutex in BoardEditorHandle set_turn")
+            .set_turn((&turn).into());
+    }
+
+    /// Sets the pieces that have already been captured
+    fn set_dead(&self, dead: Vec<Dead>) {
+        self.editor
+            .lock()
+            .expect("Poisoned mutex in BoardEditorHandle set_dead")
+            .set_dead(dead.iter().map(|dead| dead.into()).collect());
+    }
+
+    fn set_ruleset(&self, ruleset: Ruleset) {
+        self.editor
+            .lock()
+            .expect("Poisoned mutex in BoardEditorHandle set_ruleset")
+            .set_rules(ruleset.into());
+    }
+
+    /// Returns every problem with the position, which is empty if it can start a game
+    fn validate(&self) -> Vec<SetupProblem> {
+        self.editor
+            .lock()
+            .expect("Poisoned mutex in BoardEditorHandle validate")
+            .validate()
+    }
+
+    /// Starts a game from the position, if it is valid
+    fn start(&self) -> Result<Arc<GameStateHandle>, EditError> {
+        self.editor
+            .lock()
+            .expect("Poisoned mutex in BoardEditorHandle start")
+            .start()
+            .map(|game_state| Arc::new(GameStateHandle::from_state(game_state)))
+    }
+}
@@ -0,0 +1,181 @@
+use crate::ffi::{FlatPlay, GameStateHandle, Ruleset};
+use crate::game_tree::{GameTree, NodeId, TreeError};
+
+use std::sync::{Arc, Mutex};
+
+/// A handle to a tree of plays for studying a game, behind a mutex to allow calling from
+/// Kotlin without issue. Nodes are identified by numbers that stay the same until they are
+/// deleted, with the root as 0.
+#[derive(Debug, uniffi::Object)]
+pub struct GameTreeHandle {
+    tree: Mutex<GameTree>,
+}
+
+#[uniffi::export]
+impl GameTreeHandle {
+    /// Creates a tree with only the ruleset's starting position
+    #[uniffi::constructor]
+    fn new(ruleset: Ruleset) -> Self {
+        GameTreeHandle {
+            tree: Mutex::new(GameTree::new(ruleset.starting_position())),
+        }
+    }
+
+    /// Creates a tree starting from the current position of the game
+    #[uniffi::constructor]
+    fn from_game(game: Arc<GameStateHandle>) -> Self {
+        let game_state = game
+            .state
+            .lock()
+            .expect("Poisoned mutex in GameTreeHandle from_game");
+        GameTreeHandle {
+            tree: Mutex::new(GameTree::new(game_state.clone())),
+        }
+    }
+
+    /// Creates a tree from JSON that was already saved, with the end of the main line as the
+    /// current node. The tree is played from the position it was saved with, or from the
+    /// ruleset's starting position if it was saved without one.
+    #[uniffi::constructor]
+    fn load(ruleset: Ruleset, json: &str) -> Result<Self, TreeError> {
+        Ok(GameTreeHandle {
+            tree: Mutex::new(GameTree::load(ruleset.starting_position(), json)?),
+        })
+    }
+
+    /// Returns the tree as JSON in the record format, to be saved and loaded later
+    fn save(&self) -> String {
+        self.tree
+            .lock()
+            .expect("Poisoned mutex in GameTreeHandle save")
+            .save()
+    }
+
+    fn root(&self) -> u32 {
+        self.tree
+            .lock()
+            .expect("Poisoned mutex in GameTreeHandle root")
+            .root()
+            .into()
+    }
+
+    fn current(&self) -> u32 {
+        self.tree
+            .lock()
+            .expect("Poisoned mutex in GameTreeHandle current")
+            .current()
+            .into()
+    }
+
+    /// Makes the node the current one
+    fn go_to(&self, node: u32) -> Result<(), TreeError> {
+        self.tree
+            .lock()
+            .expect("Poisoned mutex in GameTreeHandle go_to")
+            .go_to(node.into())
+    }
+
+    /// Makes a play from the current node and moves to the node it leads to, which is reused
+    /// if the play was already tried from here
+    fn play(&self, play: FlatPlay) -> Result<u32, TreeError> {
+        self.tree
+            .lock()
+            .expect("Poisoned mutex in GameTreeHandle play")
+            .play(&play.into())
+            .map(|node| node.into())
+    }
+
+    /// Makes the node's line the main line from its parent
+    fn promote(&self, node: u32) -> Result<(), TreeError> {
+        self.tree
+            .lock()
+            .expect("Poisoned mutex in GameTreeHandle promote")
+            .promote(node.into())
+    }
+
+    /// Deletes the node and every line that continues from it
+    fn delete(&self, node: u32) -> Result<(), TreeError> {
+        self.tree
+            .lock()
+            .expect("Poisoned mutex in GameTreeHandle delete")
+            .delete(node.into())
+    }
+
+    /// Returns the comment on the node, if it exists
+    fn comment(&self, node: u32) -> Option<String> {
+        self.tree
+            .lock()
+            .expect("Poisoned mutex in GameTreeHandle comment")
+            .comment(node.into())
+            .map(|comment| comment.to_string())
+    }
+
+    fn set_comment(&self, node: u32, comment: String) -> Result<(), TreeError> {
+        self.tree
+            .lock()
+            .expect("Poisoned mutex in GameTreeHandle set_comment")
+            .set_comment(node.into(), comment)
+    }
+
+    /// Returns the play made to reach the node, which is None for the root
+    fn play_at(&self, node: u32) -> Option<FlatPlay> {
+        self.tree
+            .lock()
+            .expect("Poisoned mutex in GameTreeHandle play_at")
+            .play_at(node.into())
+            .map(|play| play.clone().into())
+    }
+
+    fn parent(&self, node: u32) -> Option<u32> {
+        self.tree
+            .lock()
+            .expect("Poisoned mutex in GameTreeHandle parent")
+            .parent(node.into())
+            .map(|node| node.into())
+    }
+
+    /// Returns the nodes the plays from this node lead to, with the main line first
+    fn children(&self, node: u32) -> Vec<u32> {
+        self.tree
+            .lock()
+            .expect("Poisoned mutex in GameTreeHandle children")
+            .children(node.into())
+            .iter()
+            .map(|&node| node.into())
+            .collect()
+    }
+
+    /// Returns the plays along the main line from the root
+    fn main_line(&self) -> Vec<FlatPlay> {
+        self.tree
+            .lock()
+            .expect("Poisoned mutex in GameTreeHandle main_line")
+            .main_line()
+            .into_iter()
+            .map(|play| play.into())
+            .collect()
+    }
+
+    /// Creates a handle for a copy of the game at the node, which can be played on without
+    /// changing the tree
+    fn game_at(&self, node: u32) -> Result<Arc<GameStateHandle>, TreeError> {
+        self.tree
+            .lock()
+            .expect("Poisoned mutex in GameTreeHandle game_at")
+            .state_at(node.into())
+            .map(|game_state| Arc::new(GameStateHandle::from_state(game_state.clone())))
+            .ok_or(TreeError::NoNode)
+    }
+}
+
+impl From<u32> for NodeId {
+    fn from(node: u32) -> Self {
+        NodeId(node as usize)
+    }
+}
+
+impl From<NodeId> for u32 {
+    fn from(NodeId(node): NodeId) -> Self {
+        node as u32
+    }
+}
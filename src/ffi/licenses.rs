@@ -1,6 +1,4 @@
-use crate::uniffi;
 use license_fetcher::read_package_list_from_out_dir;
-use serde_json;
 use std::error::Error;
 use std::fmt;
 
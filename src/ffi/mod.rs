@@ -1,42 +1,165 @@
-use crate::bot::minmax::min_max_play;
+use crate::bot::{Bot, Strategy};
+use crate::clock::{Clock, SystemTimeSource, TimeBank, TimeControl};
 use crate::piece::{Piece, Tile};
-use crate::state::{GameState, GameStateUpdate, Play, Player};
-use crate::uniffi;
+use crate::rules::Rules;
+use crate::state::{EndReason, GameState, GameStateUpdate, IllegalPlay, Play, Player};
+use crate::threats::{Danger, KingCall, Threats};
+use session::MatchError;
 
 use std::fmt;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use easy_ml::matrices::Matrix;
 
 mod config;
+mod editor;
+mod game_tree;
 mod licenses;
+mod network;
+mod series;
+mod session;
 
 /// A handle to the game state behind a mutex to allow calling from Kotlin without issue
 #[derive(Debug, uniffi::Object)]
 pub struct GameStateHandle {
     state: Mutex<GameState>,
+    clock: Mutex<Option<Clock>>,
+    /// Counts every change to the state, and is only changed with the state locked
+    version: AtomicU64,
+    observer: Mutex<ObserverSlot>,
+}
+
+/// Receives changes to a game as they happen, so the app doesn't have to poll the handle.
+///
+/// Every method except `on_bot_progress` is called after the handle is unlocked, on the thread
+/// that made the change, so the observer can read the new state from the handle. Progress is
+/// reported from the bot's search threads, which search a copy of the game while the handle
+/// is unlocked, so `on_bot_progress` can also call back into the handle. If the game changes
+/// during the search the bot starts again from the new position, and its progress with it.
+#[uniffi::export(with_foreign)]
+pub trait GameObserver: Send + Sync {
+    /// A play was made
+    fn on_play(&self, play: FlatPlay, outcome: PlayOutcome);
+    /// A play captured pieces
+    fn on_captures(&self, captures: Vec<Capture>);
+    /// The turn passed to the player
+    fn on_turn_changed(&self, player: TurnPlayer);
+    /// The bot searched another of its candidate plays, out of the total it is considering
+    fn on_bot_progress(&self, searched: u32, total: u32);
+    /// The game ended, and how
+    fn on_game_over(&self, winner: Winner, end_reason: EndReason);
+    /// A match couldn't carry on by itself, such as when a bot couldn't play
+    fn on_match_error(&self, error: MatchError);
+}
+
+/// The observer registered on a handle, if any, which can't derive Debug itself
+#[derive(Default)]
+struct ObserverSlot(Option<Arc<dyn GameObserver>>);
+
+impl fmt::Debug for ObserverSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(_) => write!(f, "ObserverSlot(Some(..))"),
+            None => write!(f, "ObserverSlot(None)"),
+        }
+    }
 }
 
 #[derive(Clone, Debug, uniffi::Record)]
 pub struct BotPlay {
-    game_state_update: GameStateUpdate,
+    outcome: PlayOutcome,
     play: FlatPlay,
 }
 
+/// A consistent copy of the game state at one moment
+#[derive(Clone, Debug, uniffi::Record)]
+pub struct GameSnapshot {
+    /// The tiles in row major order
+    tiles: Vec<Tile>,
+    grid_size: u8,
+    available_plays: Vec<FlatPlay>,
+    winner: Winner,
+    is_draw: bool,
+    current_player: TurnPlayer,
+    turn_count: u32,
+    dead: Vec<Dead>,
+    previous_play: Option<FlatPlay>,
+    /// The player who has offered the other a draw, if the offer is still open
+    draw_offer: Option<TurnPlayer>,
+    /// Increases with every change to the game state, so an older snapshot can be told apart
+    /// from a newer one
+    version: u64,
+}
+
+/// Everything that happened as a result of a play
+#[derive(Clone, Debug, uniffi::Record)]
+pub struct PlayOutcome {
+    game_state_update: GameStateUpdate,
+    /// The pieces captured by the play
+    captures: Vec<Capture>,
+    /// The king's move, if the play moved him
+    king_move: Option<FlatPlay>,
+    winner: Winner,
+    /// How the game ended, if the play ended it
+    end_reason: Option<EndReason>,
+}
+
+/// The winner of a finished game, which is none for a draw, and how it ended
+#[derive(Clone, Copy, Debug, uniffi::Record)]
+pub struct GameResult {
+    winner: Winner,
+    reason: EndReason,
+}
+
+/// A piece that was captured and the position it was captured on
+#[derive(Clone, Debug, uniffi::Record)]
+pub struct Capture {
+    position: FlatPosition,
+    piece: Dead,
+}
+
+/// What each side could do to the other with their next play, for showing warnings
+#[derive(Clone, Debug, uniffi::Record)]
+pub struct ThreatReport {
+    /// The king's plays straight to a corner
+    escape_routes: Vec<FlatPlay>,
+    /// Raichi or tuichi, if the king has any open paths to the corners
+    king_call: Option<KingCall>,
+    /// The pieces of either side that the other could capture with their next play
+    in_danger: Vec<PieceInDanger>,
+    /// The attackers' plays that would capture the king
+    king_captures: Vec<FlatPlay>,
+}
+
+/// A piece that could be captured, and the enemy plays that would capture it
+#[derive(Clone, Debug, uniffi::Record)]
+pub struct PieceInDanger {
+    position: FlatPosition,
+    piece: Dead,
+    by: Vec<FlatPlay>,
+}
+
 #[uniffi::export]
 impl GameStateHandle {
     #[uniffi::constructor]
     fn new() -> Self {
-        GameStateHandle {
-            state: Mutex::new(GameState::default()),
-        }
+        GameStateHandle::from_state(GameState::default())
+    }
+
+    /// Creates a handle for a game from the starting position, played with the ruleset.
+    #[uniffi::constructor]
+    fn with_ruleset(ruleset: Ruleset) -> Self {
+        GameStateHandle::from_state(ruleset.starting_position())
     }
 
     /// Creates a handle for a game that is configured based on the input arguments.
     ///
     /// The tiles are for the 11x11 row major board, and there must be a single king on the board.
     /// If the input doesn't meet the requirements, it will be silently modified with
-    /// adjusted pieces.
+    /// adjusted pieces. Use a `BoardEditorHandle` to be told what is wrong with the input
+    /// instead.
     #[uniffi::constructor]
     fn with_starting_configuration(tiles: Vec<Tile>, turn: TurnPlayer, dead: Vec<Dead>) -> Self {
         // Because we don't want to handle errors from the caller, silently pad or truncate
@@ -73,13 +196,14 @@ impl GameStateHandle {
             }
         }
 
-        GameStateHandle {
-            state: Mutex::new(GameState::from_setup(
+        GameStateHandle::from_state(
+            GameState::from_setup(
                 Matrix::from_flat_row_major((11, 11), padded_tiles),
                 (&turn).into(),
                 dead.into_iter().map(|dead| (&dead).into()).collect(),
-            )),
-        }
+            )
+            .expect("The adjusted configuration should always be valid"),
+        )
     }
 
     fn debug(&self) -> String {
@@ -117,36 +241,82 @@ impl GameStateHandle {
             .collect()
     }
 
-    /// Makes a play, if legal
-    fn make_play(&self, play: FlatPlay) -> Result<GameStateUpdate, InvalidPlayError> {
+    /// Returns the available plays for the piece at the position, which are none if there is no
+    /// piece there or it doesn't belong to the current player
+    fn plays_from(&self, x: u8, y: u8) -> Vec<FlatPlay> {
+        self.state
+            .lock()
+            .expect("Poisoned mutex in GameStateHandle plays_from")
+            .available_plays_from((x, y))
+            .into_iter()
+            .map(|play| play.into())
+            .collect()
+    }
+
+    /// Returns what making a play would do, such as what it would capture and whether it would
+    /// win, without making it. Ignores the clock.
+    fn preview_play(&self, play: FlatPlay) -> Result<PlayOutcome, InvalidPlayError> {
         let play: Play = play.into();
+        let mut preview = self
+            .state
+            .lock()
+            .expect("Poisoned mutex in GameStateHandle preview_play")
+            .clone();
+        let game_state_update = preview.make_play(&play)?;
+        Ok(PlayOutcome::new(&preview, Some(&play), game_state_update))
+    }
+
+    /// Makes a play, if legal
+    ///
+    /// If the game has a clock and the player ran out of time, the play is not made and the
+    /// loss on time is returned instead.
+    fn make_play(&self, play: FlatPlay) -> Result<PlayOutcome, InvalidPlayError> {
+        self.checked_play(play.into(), |_| Ok(()), |_| Ok(()))
+            .map(|(outcome, _)| outcome)
+    }
+
+    /// Ends a berserk chain of captures early, passing the turn to the other player.
+    fn end_turn(&self) -> Result<GameStateUpdate, InvalidPlayError> {
+        let mut state = self
+            .state
+            .lock()
+            .expect("Poisoned mutex in GameStateHandle end_turn");
+        let before = state.turn();
+        let game_state_update = match self.flag_fall(&mut state) {
+            Some(game_state_update) => game_state_update,
+            None => {
+                let game_state_update = state.end_turn()?;
+                self.changed();
+                self.press_clock(&state);
+                game_state_update
+            }
+        };
+        let outcome = PlayOutcome::new(&state, None, game_state_update);
+        let turn = turn_change(before, &state);
+        drop(state);
+        self.notify(None, &outcome, turn);
+        Ok(game_state_update)
+    }
+
+    /// Returns the piece that captured and must make the next play of a berserk chain, if
+    /// there is one. The current player can keep capturing with it or end their turn.
+    fn berserker(&self) -> Option<FlatPosition> {
         self.state
             .lock()
-            .expect("Poisoned mutex in GameStateHandle make_play")
-            .make_play(&play)
-            .map_err(|_| InvalidPlayError::Illegal)
+            .expect("Poisoned mutex in GameStateHandle berserker")
+            .berserker()
+            .map(|position| position.into())
     }
 
     /// Makes a play with the bot, if legal
     ///
     /// If a play was made successfully, also returns the play made with the
-    /// game state update.
+    /// game state update. The observer is told how far the bot has got while it searches.
+    /// If the game is over, including by the player to move running out of time, no play is
+    /// available to make.
     fn make_bot_play(&self) -> Result<BotPlay, PlayError> {
-        let mut state = self
-            .state
-            .lock()
-            .expect("Poisoned mutex in GameStateHandle make_bot_play");
-        if let Some(play) = min_max_play(&state) {
-            state
-                .make_play(&play)
-                .map_err(|_| PlayError::Illegal(InvalidPlayError::Illegal))
-                .map(|game_state_update| BotPlay {
-                    game_state_update,
-                    play: play.into(),
-                })
-        } else {
-            Err(PlayError::None(NoPlayError::None))
-        }
+        self.bot_play(|_| Some(Bot::new(Strategy::MinMax)), |_| Ok(()))?
+            .ok_or(PlayError::None(NoPlayError::None))
     }
 
     /// Returns the winner, if any
@@ -158,6 +328,14 @@ impl GameStateHandle {
             .into()
     }
 
+    /// Returns true if the game ended without a winner
+    fn is_draw(&self) -> bool {
+        self.state
+            .lock()
+            .expect("Poisoned mutex in GameStateHandle is_draw")
+            .is_draw()
+    }
+
     /// Returns the player that is making the current turn
     fn current_player(&self) -> TurnPlayer {
         self.state
@@ -167,8 +345,9 @@ impl GameStateHandle {
             .into()
     }
 
-    /// Returns the turn count. Starts at 0 with Defenders going first, odd turn counts are
-    /// Attackers' turns.
+    /// Returns the turn count. Starts at 0 with the first player's turn, which depends on the
+    /// ruleset, so even turn counts are the first player's turns and odd turn counts are the
+    /// other player's.
     fn turn_count(&self) -> u32 {
         self.state
             .lock()
@@ -176,16 +355,442 @@ impl GameStateHandle {
             .turn_count()
     }
 
+    /// Returns the player that made the first play of the game
+    fn first_player(&self) -> TurnPlayer {
+        self.state
+            .lock()
+            .expect("Poisoned mutex in GameStateHandle first_player")
+            .first_player()
+            .into()
+    }
+
+    /// Returns everything needed to draw the game in one consistent copy, which can't be
+    /// changed part way through by a play from another thread.
+    fn snapshot(&self) -> GameSnapshot {
+        let state = self
+            .state
+            .lock()
+            .expect("Poisoned mutex in GameStateHandle snapshot");
+        GameSnapshot {
+            tiles: state.tiles(),
+            grid_size: state.size().0,
+            available_plays: state
+                .available_plays()
+                .into_iter()
+                .map(|play| play.into())
+                .collect(),
+            winner: state.winner().into(),
+            is_draw: state.is_draw(),
+            current_player: state.turn().into(),
+            turn_count: state.turn_count(),
+            dead: state.dead().iter().map(|piece| piece.into()).collect(),
+            previous_play: state.last_play().map(|play| play.clone().into()),
+            draw_offer: state.draw_offer().map(|player| player.into()),
+            version: self.version.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the threats each side poses to the other with their next play
+    fn threats(&self) -> ThreatReport {
+        let threats = Threats::analyse(
+            &self
+                .state
+                .lock()
+                .expect("Poisoned mutex in GameStateHandle threats"),
+        );
+        (&threats).into()
+    }
+
     /// Returns the dead pieces
     fn dead(&self) -> Vec<Dead> {
         self.state
             .lock()
             .expect("Poisoned mutex in GameStateHandle current_player")
             .dead()
-            .into_iter()
+            .iter()
             .map(|piece| piece.into())
             .collect()
     }
+
+    /// Replaces the game clock with one for the time control, which starts counting down the
+    /// current player's time straight away if the game is still being played.
+    fn set_time_control(&self, time_control: TimeControlSettings) {
+        let state = self
+            .state
+            .lock()
+            .expect("Poisoned mutex in GameStateHandle set_time_control");
+        let mut clock = Clock::new(time_control.into(), Box::new(SystemTimeSource::new()));
+        if !state.is_over() {
+            clock.start(state.turn());
+        }
+        *self
+            .clock
+            .lock()
+            .expect("Poisoned mutex in GameStateHandle set_time_control") = Some(clock);
+    }
+
+    /// Returns the time each player has left, if the game has a clock
+    fn clock(&self) -> Option<ClockState> {
+        self.clock
+            .lock()
+            .expect("Poisoned mutex in GameStateHandle clock")
+            .as_ref()
+            .map(|clock| clock.into())
+    }
+
+    /// Checks if the current player has run out of time, ending the game if so.
+    ///
+    /// This should be called periodically while waiting for a play, since otherwise a loss on
+    /// time is only noticed when the next play is attempted.
+    fn check_clock(&self) -> GameStateUpdate {
+        let mut state = self
+            .state
+            .lock()
+            .expect("Poisoned mutex in GameStateHandle check_clock");
+        match self.flag_fall(&mut state) {
+            Some(game_state_update) => {
+                let outcome = PlayOutcome::new(&state, None, game_state_update);
+                drop(state);
+                self.notify(None, &outcome, None);
+                game_state_update
+            }
+            None => GameStateUpdate::Nothing,
+        }
+    }
+
+    /// Resigns the game for the player, whether or not it is their turn
+    fn resign(&self, player: TurnPlayer) -> Result<GameStateUpdate, InvalidPlayError> {
+        self.end_game(|state| state.resign((&player).into()))
+    }
+
+    /// Ends the game as a draw because it has gone on for longer than allowed
+    fn reach_turn_limit(&self) -> Result<GameStateUpdate, InvalidPlayError> {
+        self.end_game(|state| state.reach_turn_limit())
+    }
+
+    /// Offers the other player a draw, which stays open until they answer it or play on
+    ///
+    /// If the game has a clock and the player to move ran out of time, the game is lost on time
+    /// and no draw can be offered.
+    fn offer_draw(&self, player: TurnPlayer) -> Result<(), InvalidPlayError> {
+        let mut state = self
+            .state
+            .lock()
+            .expect("Poisoned mutex in GameStateHandle offer_draw");
+        let timed_out = self
+            .flag_fall(&mut state)
+            .map(|game_state_update| PlayOutcome::new(&state, None, game_state_update));
+        let offered = state.offer_draw((&player).into());
+        if offered.is_ok() {
+            self.changed();
+        }
+        drop(state);
+        if let Some(outcome) = timed_out {
+            self.notify(None, &outcome, None);
+        }
+        Ok(offered?)
+    }
+
+    /// Accepts the other player's draw offer, ending the game
+    fn accept_draw(&self, player: TurnPlayer) -> Result<GameStateUpdate, InvalidPlayError> {
+        self.end_game(|state| state.accept_draw((&player).into()))
+    }
+
+    /// Declines the other player's draw offer
+    fn decline_draw(&self, player: TurnPlayer) -> Result<(), InvalidPlayError> {
+        let mut state = self
+            .state
+            .lock()
+            .expect("Poisoned mutex in GameStateHandle decline_draw");
+        state.decline_draw((&player).into())?;
+        self.changed();
+        Ok(())
+    }
+
+    /// Returns the player who has offered the other a draw, if the offer is still open
+    fn draw_offer(&self) -> Option<TurnPlayer> {
+        self.state
+            .lock()
+            .expect("Poisoned mutex in GameStateHandle draw_offer")
+            .draw_offer()
+            .map(|player| player.into())
+    }
+
+    /// Returns the winner and how the game ended, if it is over
+    fn result(&self) -> Option<GameResult> {
+        self.state
+            .lock()
+            .expect("Poisoned mutex in GameStateHandle result")
+            .result()
+            .map(|result| result.into())
+    }
+
+    /// Registers the observer to be told about every change to the game from now on,
+    /// replacing any previous observer.
+    fn set_observer(&self, observer: Arc<dyn GameObserver>) {
+        self.observer
+            .lock()
+            .expect("Poisoned mutex in GameStateHandle set_observer")
+            .0 = Some(observer);
+    }
+
+    /// Stops telling the registered observer, if any, about changes to the game
+    fn clear_observer(&self) {
+        self.observer
+            .lock()
+            .expect("Poisoned mutex in GameStateHandle clear_observer")
+            .0 = None;
+    }
+}
+
+impl GameStateHandle {
+    /// Records a loss on time if the current player's clock has run out. Must be called with
+    /// the state already locked.
+    fn flag_fall(&self, state: &mut GameState) -> Option<GameStateUpdate> {
+        let mut clock = self
+            .clock
+            .lock()
+            .expect("Poisoned mutex in GameStateHandle flag_fall");
+        let player = clock.as_mut()?.check()?;
+        let game_state_update = state.time_out(player).ok()?;
+        self.changed();
+        Some(game_state_update)
+    }
+
+    /// Makes a play with the bot chosen for the state, if any, telling the observer how far it
+    /// has got while it searches. The bot searches a copy of the state so the game stays
+    /// readable in the meantime, and searches again if something else changed the game first.
+    /// The play is only made once `confirm` accepts it, so a play it refuses leaves the game
+    /// as it was. Returns None if the game is over, including when the player to move runs
+    /// out of time.
+    fn bot_play<E: From<PlayError>>(
+        &self,
+        choose: impl Fn(&GameState) -> Option<Bot>,
+        confirm: impl Fn(&Play) -> Result<(), E>,
+    ) -> Result<Option<BotPlay>, E> {
+        loop {
+            let (bot, searching, version) = {
+                let mut state = self
+                    .state
+                    .lock()
+                    .expect("Poisoned mutex in GameStateHandle bot_play");
+                if let Some(game_state_update) = self.flag_fall(&mut state) {
+                    let outcome = PlayOutcome::new(&state, None, game_state_update);
+                    drop(state);
+                    self.notify(None, &outcome, None);
+                    return Ok(None);
+                }
+                let Some(bot) = choose(&state) else {
+                    return Ok(None);
+                };
+                (bot, state.clone(), self.version.load(Ordering::Relaxed))
+            };
+            let play = bot
+                .play_with_progress(&searching, &|searched, total| {
+                    if let Some(observer) = self.observer() {
+                        observer.on_bot_progress(searched, total);
+                    }
+                })
+                .ok_or(PlayError::None(NoPlayError::None))?;
+            let mut state = self
+                .state
+                .lock()
+                .expect("Poisoned mutex in GameStateHandle bot_play");
+            if self.version.load(Ordering::Relaxed) != version {
+                continue;
+            }
+            let before = state.turn();
+            // The search may have used up the rest of the bot's time
+            if let Some(game_state_update) = self.flag_fall(&mut state) {
+                let outcome = PlayOutcome::new(&state, None, game_state_update);
+                drop(state);
+                self.notify(None, &outcome, None);
+                return Ok(None);
+            }
+            let mut played = state.clone();
+            let game_state_update = played
+                .make_play(&play)
+                .map_err(|reason| PlayError::Illegal(reason.into()))?;
+            confirm(&play)?;
+            *state = played;
+            self.changed();
+            self.press_clock(&state);
+            let outcome = PlayOutcome::new(&state, Some(&play), game_state_update);
+            let turn = turn_change(before, &state);
+            drop(state);
+            self.notify(Some(&play), &outcome, turn);
+            return Ok(Some(BotPlay {
+                outcome,
+                play: play.into(),
+            }));
+        }
+    }
+
+    /// Makes a play like `make_play` once the check of the state allows it, keeping the state
+    /// locked throughout so that nothing else can play in between. A legal play is only made
+    /// once `confirm` accepts it, so a play it refuses leaves the game as it was. Also returns
+    /// whether the play was made, which it isn't if the player ran out of time.
+    fn checked_play<E: From<InvalidPlayError>>(
+        &self,
+        play: Play,
+        check: impl FnOnce(&GameState) -> Result<(), E>,
+        confirm: impl FnOnce(&Play) -> Result<(), E>,
+    ) -> Result<(PlayOutcome, bool), E> {
+        let mut state = self
+            .state
+            .lock()
+            .expect("Poisoned mutex in GameStateHandle checked_play");
+        check(&state)?;
+        let before = state.turn();
+        let (outcome, made) = match self.flag_fall(&mut state) {
+            Some(game_state_update) => (PlayOutcome::new(&state, None, game_state_update), false),
+            None => {
+                let mut played = state.clone();
+                let game_state_update = played.make_play(&play).map_err(InvalidPlayError::from)?;
+                confirm(&play)?;
+                *state = played;
+                self.changed();
+                self.press_clock(&state);
+                (
+                    PlayOutcome::new(&state, Some(&play), game_state_update),
+                    true,
+                )
+            }
+        };
+        let turn = turn_change(before, &state);
+        drop(state);
+        self.notify(made.then_some(&play), &outcome, turn);
+        Ok((outcome, made))
+    }
+
+    /// Creates a handle for the game state, without a clock
+    fn from_state(state: GameState) -> Self {
+        GameStateHandle {
+            state: Mutex::new(state),
+            clock: Mutex::new(None),
+            version: AtomicU64::new(0),
+            observer: Mutex::new(ObserverSlot::default()),
+        }
+    }
+
+    /// Ends the game by an action other than a play, such as resigning, stopping the clock and
+    /// telling the observer. If the player to move already ran out of time the action isn't
+    /// taken and the loss on time is returned instead.
+    fn end_game(
+        &self,
+        action: impl FnOnce(&mut GameState) -> Result<GameStateUpdate, IllegalPlay>,
+    ) -> Result<GameStateUpdate, InvalidPlayError> {
+        let mut state = self
+            .state
+            .lock()
+            .expect("Poisoned mutex in GameStateHandle end_game");
+        let game_state_update = match self.flag_fall(&mut state) {
+            Some(game_state_update) => game_state_update,
+            None => {
+                let game_state_update = action(&mut state)?;
+                self.changed();
+                self.press_clock(&state);
+                game_state_update
+            }
+        };
+        let outcome = PlayOutcome::new(&state, None, game_state_update);
+        drop(state);
+        self.notify(None, &outcome, None);
+        Ok(game_state_update)
+    }
+
+    /// Counts a change to the state. Must be called with the state already locked.
+    fn changed(&self) {
+        self.version.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the registered observer, if any, without keeping it locked so that it can be
+    /// replaced from inside its own callbacks.
+    fn observer(&self) -> Option<Arc<dyn GameObserver>> {
+        self.observer
+            .lock()
+            .expect("Poisoned mutex in GameStateHandle observer")
+            .0
+            .clone()
+    }
+
+    /// Tells the observer, if any, about a play or the game ending before one could be made.
+    /// Must be called with the state unlocked, so that the observer can read it.
+    fn notify(&self, play: Option<&Play>, outcome: &PlayOutcome, turn: Option<Player>) {
+        let Some(observer) = self.observer() else {
+            return;
+        };
+        if let Some(play) = play {
+            observer.on_play(play.clone().into(), outcome.clone());
+        }
+        if !outcome.captures.is_empty() {
+            observer.on_captures(outcome.captures.clone());
+        }
+        if let Some(turn) = turn {
+            observer.on_turn_changed(turn.into());
+        }
+        if let Some(end_reason) = outcome.end_reason {
+            observer.on_game_over(outcome.winner, end_reason);
+        }
+    }
+
+    /// Switches the clock over to the next player after a play, or stops it if the play ended
+    /// the game. The clock keeps running if the play didn't end the turn. Must be called with
+    /// the state already locked.
+    fn press_clock(&self, state: &GameState) {
+        let mut clock = self
+            .clock
+            .lock()
+            .expect("Poisoned mutex in GameStateHandle press_clock");
+        if let Some(clock) = clock.as_mut() {
+            if state.is_over() {
+                clock.stop();
+            } else if clock.running() != Some(state.turn()) {
+                // We checked for the player running out of time just before the play so can
+                // ignore them going over in between, which will be caught on the next check
+                let _ = clock.press();
+            }
+        }
+    }
+}
+
+/// Returns the player whose turn it now is if that changed from before, and the game is still
+/// going.
+fn turn_change(before: Player, state: &GameState) -> Option<Player> {
+    if state.is_over() || state.turn() == before {
+        None
+    } else {
+        Some(state.turn())
+    }
+}
+
+impl From<crate::state::GameResult> for GameResult {
+    fn from(result: crate::state::GameResult) -> Self {
+        GameResult {
+            winner: result.winner.into(),
+            reason: result.reason,
+        }
+    }
+}
+
+impl From<&Threats> for ThreatReport {
+    fn from(threats: &Threats) -> Self {
+        let plays = |plays: &[Play]| plays.iter().map(|play| play.clone().into()).collect();
+        ThreatReport {
+            escape_routes: plays(&threats.escape_routes),
+            king_call: threats.king_call(),
+            in_danger: threats
+                .in_danger
+                .iter()
+                .map(|danger: &Danger| PieceInDanger {
+                    position: danger.position.into(),
+                    piece: (&danger.piece).into(),
+                    by: plays(&danger.by),
+                })
+                .collect(),
+            king_captures: plays(&threats.king_captures),
+        }
+    }
 }
 
 /// A flattened representation of a Play, consisting of 4 u8s for a total size of 4 bytes
@@ -218,19 +823,67 @@ impl From<FlatPlay> for Play {
     }
 }
 
+impl PlayOutcome {
+    /// Describes the play just made on the state, or the game ending before it could be made.
+    fn new(state: &GameState, play: Option<&Play>, game_state_update: GameStateUpdate) -> Self {
+        let captures = match play {
+            Some(_) => state
+                .last_captures()
+                .iter()
+                .map(|(position, piece)| Capture {
+                    position: (*position).into(),
+                    piece: piece.into(),
+                })
+                .collect(),
+            None => vec![],
+        };
+        let king_move = play
+            .filter(|play| state.king_position() == play.to)
+            .map(|play| play.clone().into());
+        PlayOutcome {
+            game_state_update,
+            captures,
+            king_move,
+            winner: state.winner().into(),
+            end_reason: state.end_reason(),
+        }
+    }
+}
+
+/// A position on the board
+#[derive(Clone, Debug, uniffi::Record)]
+pub struct FlatPosition {
+    pub x: u8,
+    pub y: u8,
+}
+
+impl From<(u8, u8)> for FlatPosition {
+    fn from((x, y): (u8, u8)) -> Self {
+        FlatPosition { x, y }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, uniffi::Enum)]
-enum InvalidPlayError {
-    Illegal,
+pub enum InvalidPlayError {
+    Illegal(IllegalPlay),
 }
 
 impl fmt::Display for InvalidPlayError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Play is illegal")
+        match self {
+            InvalidPlayError::Illegal(reason) => write!(f, "Play is illegal: {}", reason),
+        }
+    }
+}
+
+impl From<IllegalPlay> for InvalidPlayError {
+    fn from(reason: IllegalPlay) -> Self {
+        InvalidPlayError::Illegal(reason)
     }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, uniffi::Enum)]
-enum NoPlayError {
+pub enum NoPlayError {
     None,
 }
 
@@ -241,7 +894,7 @@ impl fmt::Display for NoPlayError {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, uniffi::Enum)]
-enum PlayError {
+pub enum PlayError {
     Illegal(InvalidPlayError),
     None(NoPlayError),
 }
@@ -304,6 +957,8 @@ pub enum Dead {
     Attacker = 0,
     Defender = 1,
     King = 2,
+    Commander = 3,
+    Knight = 4,
 }
 
 impl From<&Piece> for Dead {
@@ -312,6 +967,8 @@ impl From<&Piece> for Dead {
             Piece::Attacker => Dead::Attacker,
             Piece::Defender => Dead::Defender,
             Piece::King => Dead::King,
+            Piece::Commander => Dead::Commander,
+            Piece::Knight => Dead::Knight,
         }
     }
 }
@@ -322,6 +979,100 @@ impl From<&Dead> for Piece {
             Dead::Attacker => Piece::Attacker,
             Dead::Defender => Piece::Defender,
             Dead::King => Piece::King,
+            Dead::Commander => Piece::Commander,
+            Dead::Knight => Piece::Knight,
+        }
+    }
+}
+
+/// The tafl variant a game is played with
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, uniffi::Enum)]
+pub enum Ruleset {
+    /// The rules this game was originally written with
+    Standard = 0,
+    Copenhagen = 1,
+    /// Berserk Hnefatafl, which also has its own starting position
+    Berserk = 2,
+}
+
+impl Ruleset {
+    /// Returns the ruleset's starting position, played with its rules
+    fn starting_position(self) -> GameState {
+        match self {
+            Ruleset::Berserk => GameState::berserk(),
+            _ => GameState::new(self.into()),
+        }
+    }
+}
+
+impl From<Ruleset> for Rules {
+    fn from(value: Ruleset) -> Self {
+        match value {
+            Ruleset::Standard => Rules::default(),
+            Ruleset::Copenhagen => Rules::copenhagen(),
+            Ruleset::Berserk => Rules::berserk(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, uniffi::Enum)]
+pub enum TimeControlSettings {
+    /// Each player starts with the initial time and gains the increment after every play.
+    Fischer { initial_ms: u64, increment_ms: u64 },
+    /// Each player starts with the initial time, after which they have a number of periods to
+    /// make each play in, which are only used up if they run out.
+    ByoYomi {
+        initial_ms: u64,
+        period_ms: u64,
+        periods: u32,
+    },
+}
+
+impl From<TimeControlSettings> for TimeControl {
+    fn from(value: TimeControlSettings) -> Self {
+        match value {
+            TimeControlSettings::Fischer {
+                initial_ms,
+                increment_ms,
+            } => TimeControl::Fischer {
+                initial: Duration::from_millis(initial_ms),
+                increment: Duration::from_millis(increment_ms),
+            },
+            TimeControlSettings::ByoYomi {
+                initial_ms,
+                period_ms,
+                periods,
+            } => TimeControl::ByoYomi {
+                initial: Duration::from_millis(initial_ms),
+                period: Duration::from_millis(period_ms),
+                periods,
+            },
+        }
+    }
+}
+
+/// The time each player has left, where the periods are always 0 for Fischer time controls
+#[derive(Clone, Debug, uniffi::Record)]
+pub struct ClockState {
+    attackers_ms: u64,
+    attackers_periods: u32,
+    defenders_ms: u64,
+    defenders_periods: u32,
+    /// The player whose time is counting down, if any
+    running: Option<TurnPlayer>,
+}
+
+impl From<&Clock> for ClockState {
+    fn from(clock: &Clock) -> Self {
+        let attackers: TimeBank = clock.remaining(Player::Attacker);
+        let defenders: TimeBank = clock.remaining(Player::Defender);
+        ClockState {
+            attackers_ms: attackers.main.as_millis() as u64,
+            attackers_periods: attackers.periods,
+            defenders_ms: defenders.main.as_millis() as u64,
+            defenders_periods: defenders.periods,
+            running: clock.running().map(|player| player.into()),
         }
     }
 }
@@ -0,0 +1,179 @@
+use crate::ffi::{Dead, FlatPlay, TurnPlayer, Winner};
+use crate::network::client::Client;
+use crate::network::server::{Server, Stopper};
+use crate::network::NetworkError;
+use crate::piece::Tile;
+use crate::state::Play;
+
+use std::fmt;
+use std::sync::Arc;
+use std::thread;
+
+/// A handle to a seat in a game on a network server, to allow calling from Kotlin without issue
+#[derive(Debug, uniffi::Object)]
+pub struct NetworkGameHandle {
+    client: Client,
+}
+
+#[uniffi::export]
+impl NetworkGameHandle {
+    /// Connects to the server at the address (such as `192.168.0.2:7878`) and takes a seat in
+    /// the named game, which is created if nobody has joined it yet.
+    ///
+    /// To rejoin after a disconnect, pass the token from the previous handle.
+    #[uniffi::constructor]
+    fn connect(
+        address: String,
+        game: String,
+        seat: TurnPlayer,
+        token: Option<String>,
+    ) -> Result<Self, NetworkGameError> {
+        Ok(NetworkGameHandle {
+            client: Client::connect(address, &game, (&seat).into(), token)?,
+        })
+    }
+
+    /// Returns the token needed to reclaim this seat after a disconnect
+    fn token(&self) -> String {
+        self.client.token().to_string()
+    }
+
+    /// Returns the player this handle is seated as
+    fn seat(&self) -> TurnPlayer {
+        self.client.seat().into()
+    }
+
+    /// Returns true while the connection to the server is open
+    fn is_connected(&self) -> bool {
+        self.client.state().connected
+    }
+
+    /// Returns the number of plays made so far, which changes whenever either seat plays
+    fn play_count(&self) -> u32 {
+        self.client.state().plays.len() as u32
+    }
+
+    /// Returns the reason the server rejected our last play, if it did
+    fn rejection(&self) -> Option<String> {
+        self.client.state().rejection
+    }
+
+    /// Returns the tiles in row major order
+    fn tiles(&self) -> Vec<Tile> {
+        self.client.state().game_state.tiles()
+    }
+
+    /// Returns the length of one side of the square grid
+    fn grid_size(&self) -> u8 {
+        self.client.state().game_state.size().0
+    }
+
+    /// Returns the available plays for the player making the current turn
+    fn available_plays(&self) -> Vec<FlatPlay> {
+        self.client
+            .state()
+            .game_state
+            .available_plays()
+            .into_iter()
+            .map(|play| play.into())
+            .collect()
+    }
+
+    /// Sends a play to the server, which takes effect once the server has accepted it
+    fn make_play(&self, play: FlatPlay) -> Result<(), NetworkGameError> {
+        let play: Play = play.into();
+        Ok(self.client.make_play(play)?)
+    }
+
+    /// Returns the winner, if any
+    fn winner(&self) -> Winner {
+        self.client.state().game_state.winner().into()
+    }
+
+    /// Returns the player that is making the current turn
+    fn current_player(&self) -> TurnPlayer {
+        self.client.state().game_state.turn().into()
+    }
+
+    /// Returns the turn count
+    fn turn_count(&self) -> u32 {
+        self.client.state().game_state.turn_count()
+    }
+
+    /// Returns the dead pieces
+    fn dead(&self) -> Vec<Dead> {
+        self.client
+            .state()
+            .game_state
+            .dead()
+            .iter()
+            .map(|piece| piece.into())
+            .collect()
+    }
+}
+
+/// A handle to a game server running on a background thread
+#[derive(Debug, uniffi::Object)]
+pub struct ServerHandle {
+    address: String,
+    stopper: Stopper,
+}
+
+#[uniffi::export]
+impl ServerHandle {
+    /// Returns the address the server is listening on
+    fn address(&self) -> String {
+        self.address.clone()
+    }
+
+    /// Stops the server, disconnecting everyone playing on it
+    fn stop(&self) {
+        self.stopper.stop();
+    }
+}
+
+/// Starts a game server on a background thread, returning a handle to stop it with.
+///
+/// Binding to `0.0.0.0:0` listens on every network interface with a free port.
+#[uniffi::export]
+fn host_server(address: String) -> Result<Arc<ServerHandle>, NetworkGameError> {
+    let server = Server::bind(address).map_err(NetworkError::Io)?;
+    let local_address = server.local_addr().map_err(NetworkError::Io)?;
+    let stopper = server.stopper().map_err(NetworkError::Io)?;
+    thread::spawn(move || {
+        if let Err(error) = server.run() {
+            eprintln!("Error running server: {}", error);
+        }
+    });
+    Ok(Arc::new(ServerHandle {
+        address: local_address.to_string(),
+        stopper,
+    }))
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, uniffi::Enum)]
+pub enum NetworkGameError {
+    Io(String),
+    Rejected(String),
+    Disconnected,
+}
+
+impl fmt::Display for NetworkGameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkGameError::Io(error) => write!(f, "Network error: {}", error),
+            NetworkGameError::Rejected(reason) => write!(f, "Rejected by server: {}", reason),
+            NetworkGameError::Disconnected => write!(f, "Disconnected from server"),
+        }
+    }
+}
+
+impl From<NetworkError> for NetworkGameError {
+    fn from(error: NetworkError) -> Self {
+        match error {
+            NetworkError::Io(error) => NetworkGameError::Io(error.to_string()),
+            NetworkError::Rejected(reason) => NetworkGameError::Rejected(reason),
+            NetworkError::Disconnected => NetworkGameError::Disconnected,
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use crate::ffi::{FlatPlay, GameStateHandle, Ruleset, TurnPlayer};
+use crate::series::{Participant, Series, SeriesError, Standing};
+
+use std::sync::{Arc, Mutex};
+
+/// A handle to a series of games where the players swap sides after every game, behind a
+/// mutex to allow calling from Kotlin without issue
+#[derive(Debug, uniffi::Object)]
+pub struct SeriesHandle {
+    series: Mutex<Series>,
+}
+
+#[uniffi::export]
+impl SeriesHandle {
+    /// Creates a series of the number of games, each played from the ruleset's starting
+    /// position.
+    #[uniffi::constructor]
+    fn new(ruleset: Ruleset, games: u32) -> Self {
+        SeriesHandle {
+            series: Mutex::new(Series::new(games, ruleset.into())),
+        }
+    }
+
+    /// Creates a series handle from JSON that was already saved
+    #[uniffi::constructor]
+    fn load(json: &str) -> Result<Self, SeriesError> {
+        Ok(SeriesHandle {
+            series: Mutex::new(Series::load(json)?),
+        })
+    }
+
+    /// Returns the series as JSON, to be saved and loaded later
+    fn save(&self) -> String {
+        self.series
+            .lock()
+            .expect("Poisoned mutex in SeriesHandle save")
+            .save()
+    }
+
+    /// Returns the number of games in the series
+    fn games(&self) -> u32 {
+        self.series
+            .lock()
+            .expect("Poisoned mutex in SeriesHandle games")
+            .games
+    }
+
+    /// Returns the index of the game to be played next, if the series isn't over
+    fn current_game(&self) -> Option<u32> {
+        self.series
+            .lock()
+            .expect("Poisoned mutex in SeriesHandle current_game")
+            .current_game()
+            .map(|game| game as u32)
+    }
+
+    /// Returns true once every game in the series has been played
+    fn is_over(&self) -> bool {
+        self.series
+            .lock()
+            .expect("Poisoned mutex in SeriesHandle is_over")
+            .is_over()
+    }
+
+    /// Returns the side the participant plays in the game at the index
+    fn side(&self, participant: Participant, game: u32) -> TurnPlayer {
+        Series::side(participant, game as usize).into()
+    }
+
+    /// Creates a handle for the next game in the series
+    fn new_game(&self) -> Arc<GameStateHandle> {
+        Arc::new(GameStateHandle::from_state(
+            self.series
+                .lock()
+                .expect("Poisoned mutex in SeriesHandle new_game")
+                .new_game(),
+        ))
+    }
+
+    /// Adds a finished game to the series, with the plays that were made in it. Games still
+    /// being played should be ended first, such as with `GameStateHandle::reach_turn_limit`.
+    /// The plays are replayed to score the game, and it is refused if they aren't legal or
+    /// don't lead to the game's position.
+    fn record_game(
+        &self,
+        game: Arc<GameStateHandle>,
+        plays: Vec<FlatPlay>,
+    ) -> Result<(), SeriesError> {
+        let game_state = game
+            .state
+            .lock()
+            .expect("Poisoned mutex in SeriesHandle record_game");
+        self.series
+            .lock()
+            .expect("Poisoned mutex in SeriesHandle record_game")
+            .record_game(
+                plays.into_iter().map(|play| play.into()).collect(),
+                &game_state,
+            )
+    }
+
+    /// Returns how the participant is doing in the games played so far
+    fn standing(&self, participant: Participant) -> Standing {
+        self.series
+            .lock()
+            .expect("Poisoned mutex in SeriesHandle standing")
+            .standing(participant)
+    }
+
+    /// Returns the participant ahead on wins, or on speed of winning if they have the same
+    /// score, or None if they are level on both
+    fn leader(&self) -> Option<Participant> {
+        self.series
+            .lock()
+            .expect("Poisoned mutex in SeriesHandle leader")
+            .leader()
+    }
+}
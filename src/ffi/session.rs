@@ -0,0 +1,365 @@
+use crate::bot::{minmax, Bot, Strategy};
+use crate::ffi::network::NetworkGameError;
+use crate::ffi::{
+    BotPlay, FlatPlay, GameObserver, GameSnapshot, GameStateHandle, InvalidPlayError, PlayError,
+    PlayOutcome, Ruleset, TurnPlayer,
+};
+use crate::network::client::Client;
+use crate::rules::Rules;
+use crate::session::{Seat, Seats};
+use crate::state::{IllegalPlay, Play, Player};
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// A game between two seats, which makes the bots' plays by itself so the app only has to
+/// pass on the plays of human seats. Once connected to a server, plays for the remote seat
+/// arrive from there and every other play is sent to it.
+#[derive(Debug, uniffi::Object)]
+pub struct MatchHandle {
+    game: GameStateHandle,
+    seats: Seats,
+    /// Whether a thread is making the bots' plays
+    bots_running: Mutex<bool>,
+    remote: Mutex<Option<Arc<Remote>>>,
+}
+
+/// The connection to the server the remote seat plays through
+#[derive(Debug)]
+struct Remote {
+    client: Client,
+    /// The number of plays made in the match, which the server has or will soon have too
+    plays: Mutex<usize>,
+}
+
+#[uniffi::export]
+impl MatchHandle {
+    /// Creates a match with the ruleset's starting position. Call `start` once the app is
+    /// ready for the bots to begin.
+    #[uniffi::constructor]
+    fn new(ruleset: Ruleset, attackers: SeatType, defenders: SeatType) -> Self {
+        MatchHandle {
+            game: GameStateHandle::with_ruleset(ruleset),
+            seats: Seats {
+                attackers: attackers.into(),
+                defenders: defenders.into(),
+            },
+            bots_running: Mutex::new(false),
+            remote: Mutex::new(None),
+        }
+    }
+
+    /// Returns a consistent copy of the game state
+    fn snapshot(&self) -> GameSnapshot {
+        self.game.snapshot()
+    }
+
+    /// Registers the observer to be told about every change to the game from now on,
+    /// including problems the match runs into by itself, replacing any previous observer.
+    fn set_observer(&self, observer: Arc<dyn GameObserver>) {
+        self.game.set_observer(observer);
+    }
+
+    /// Stops telling the registered observer, if any, about changes to the game
+    fn clear_observer(&self) {
+        self.game.clear_observer();
+    }
+
+    /// Returns the seat that plays for the player
+    fn seat(&self, player: TurnPlayer) -> SeatType {
+        self.seats.seat((&player).into()).into()
+    }
+
+    /// Makes the bots' plays on a background thread until it is a human or remote seat's
+    /// turn, or the game is over. Does nothing if it isn't a bot's turn, or the bots are
+    /// already playing. If a bot can't play the observer is told why.
+    fn start(self: Arc<Self>) {
+        let mut running = self
+            .bots_running
+            .lock()
+            .expect("Poisoned mutex in MatchHandle start");
+        if *running {
+            return;
+        }
+        *running = true;
+        drop(running);
+        let bots = self.clone();
+        thread::spawn(move || loop {
+            let stopped = match bots.step() {
+                Ok(Some(_)) => continue,
+                Ok(None) => None,
+                Err(error) => Some(error),
+            };
+            let mut running = bots
+                .bots_running
+                .lock()
+                .expect("Poisoned mutex in MatchHandle start");
+            // A play made while we were stopping may have handed the turn back to a bot,
+            // which would otherwise wait for a thread that has already decided not to run
+            if stopped.is_none() && bots.is_bots_turn() {
+                continue;
+            }
+            *running = false;
+            drop(running);
+            if let Some(error) = stopped {
+                bots.report(error);
+            }
+            break;
+        });
+    }
+
+    /// Makes a play for the human seat whose turn it is, then starts the bots if it is their
+    /// turn next. Once connected to a server the play is only made after the server accepts
+    /// it, and its reason for rejecting the play is returned otherwise.
+    fn make_play(self: Arc<Self>, play: FlatPlay) -> Result<PlayOutcome, MatchError> {
+        let (outcome, _) = self.game.checked_play(
+            play.into(),
+            |state| match self.seats.seat(state.turn()) {
+                Seat::Human => Ok(()),
+                Seat::Bot(_) => Err(MatchError::BotsTurn),
+                Seat::Remote => Err(MatchError::RemotesTurn),
+            },
+            |play| self.send(play),
+        )?;
+        self.start();
+        Ok(outcome)
+    }
+
+    /// Connects to the server at the address and takes the seat opposite the remote seat in
+    /// the named game, returning the token needed to reclaim it after a disconnect. From then
+    /// on the remote seat's plays are made as they arrive from the server.
+    ///
+    /// The server plays with the default rules from the starting position, so the match
+    /// must too, and must connect before any plays are made.
+    fn connect(
+        self: Arc<Self>,
+        address: String,
+        game: String,
+        token: Option<String>,
+    ) -> Result<String, MatchError> {
+        let remote = match (self.seats.attackers, self.seats.defenders) {
+            (Seat::Remote, Seat::Remote) => return Err(MatchError::RemoteSeats),
+            (Seat::Remote, _) => Player::Attacker,
+            (_, Seat::Remote) => Player::Defender,
+            _ => return Err(MatchError::RemoteSeats),
+        };
+        {
+            let state = self
+                .game
+                .state
+                .lock()
+                .expect("Poisoned mutex in MatchHandle connect");
+            if state.rules() != Rules::default() || state.turn_count() > 0 {
+                return Err(MatchError::NotAtStart);
+            }
+        }
+        let client = Client::connect(address, &game, remote.next(), token)
+            .map_err(|error| MatchError::Network(error.into()))?;
+        let token = client.token().to_string();
+        let remote = Arc::new(Remote {
+            client,
+            plays: Mutex::new(0),
+        });
+        *self
+            .remote
+            .lock()
+            .expect("Poisoned mutex in MatchHandle connect") = Some(remote.clone());
+        thread::spawn(move || self.follow(&remote));
+        Ok(token)
+    }
+
+    /// Makes the next play on this thread if it's a bot's turn, returning None if it is a
+    /// human or remote seat's turn or the game is over.
+    fn step(&self) -> Result<Option<BotPlay>, MatchError> {
+        self.game.bot_play(
+            |state| self.seats.bot_to_move(state),
+            |play| self.send(play),
+        )
+    }
+}
+
+impl MatchHandle {
+    fn is_bots_turn(&self) -> bool {
+        let state = self
+            .game
+            .state
+            .lock()
+            .expect("Poisoned mutex in MatchHandle is_bots_turn");
+        self.seats.bot_to_move(&state).is_some()
+    }
+
+    fn remote(&self) -> Option<Arc<Remote>> {
+        self.remote
+            .lock()
+            .expect("Poisoned mutex in MatchHandle remote")
+            .clone()
+    }
+
+    /// Tells the observer, if any, about a problem the match ran into by itself
+    fn report(&self, error: MatchError) {
+        if let Some(observer) = self.game.observer() {
+            observer.on_match_error(error);
+        }
+    }
+
+    /// Sends a play about to be made in the match to the server, if connected to one, and
+    /// waits for the server to accept it
+    fn send(&self, play: &Play) -> Result<(), MatchError> {
+        let Some(remote) = self.remote() else {
+            return Ok(());
+        };
+        // Counted before sending so the server's echo of the play is known to be ours
+        *remote
+            .plays
+            .lock()
+            .expect("Poisoned mutex in MatchHandle send") += 1;
+        let sent = remote
+            .client
+            .make_play_and_wait(play.clone())
+            .map_err(|error| MatchError::Network(error.into()));
+        if sent.is_err() {
+            *remote
+                .plays
+                .lock()
+                .expect("Poisoned mutex in MatchHandle send") -= 1;
+        }
+        sent
+    }
+
+    /// Makes the remote seat's plays as the server sends them, until the connection closes
+    fn follow(self: Arc<Self>, remote: &Remote) {
+        let mut seen = 0;
+        while let Some(plays) = remote.client.wait_for_plays(seen) {
+            for play in &plays[seen..] {
+                seen += 1;
+                let made = *remote
+                    .plays
+                    .lock()
+                    .expect("Poisoned mutex in MatchHandle follow");
+                if seen <= made {
+                    // The server is telling us about a play made here
+                    continue;
+                }
+                let result = self.game.checked_play(
+                    play.clone(),
+                    |state| match self.seats.seat(state.turn()) {
+                        Seat::Remote => Ok(()),
+                        _ => Err(MatchError::OutOfTurn),
+                    },
+                    |_| Ok(()),
+                );
+                match result {
+                    Ok(_) => {
+                        *remote
+                            .plays
+                            .lock()
+                            .expect("Poisoned mutex in MatchHandle follow") += 1
+                    }
+                    Err(error) => self.report(error),
+                }
+            }
+            self.clone().start();
+        }
+    }
+}
+
+/// Who makes the plays for one side of a match
+#[derive(Clone, Copy, Debug, Eq, PartialEq, uniffi::Enum)]
+pub enum SeatType {
+    /// A person playing on this device
+    Human,
+    /// A bot, which searches depth plays ahead if it uses min-max, from 1 up to 5
+    Bot { strategy: BotStrategy, depth: u8 },
+    /// A player somewhere else, whose plays arrive over the network
+    Remote,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, uniffi::Enum)]
+pub enum BotStrategy {
+    MinMax,
+    Random,
+}
+
+impl From<SeatType> for Seat {
+    fn from(seat: SeatType) -> Self {
+        match seat {
+            SeatType::Human => Seat::Human,
+            SeatType::Bot { strategy, depth } => Seat::Bot(Bot {
+                strategy: match strategy {
+                    BotStrategy::MinMax => Strategy::MinMax,
+                    BotStrategy::Random => Strategy::Random,
+                },
+                depth: depth.clamp(1, minmax::MAX_DEPTH),
+            }),
+            SeatType::Remote => Seat::Remote,
+        }
+    }
+}
+
+impl From<Seat> for SeatType {
+    fn from(seat: Seat) -> Self {
+        match seat {
+            Seat::Human => SeatType::Human,
+            Seat::Bot(bot) => SeatType::Bot {
+                strategy: match bot.strategy {
+                    Strategy::MinMax => BotStrategy::MinMax,
+                    Strategy::Random => BotStrategy::Random,
+                },
+                depth: bot.depth,
+            },
+            Seat::Remote => SeatType::Remote,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, uniffi::Enum)]
+pub enum MatchError {
+    /// Plays for bot seats are made by the match itself
+    BotsTurn,
+    /// Plays for the remote seat arrive from the server
+    RemotesTurn,
+    /// The server sent a play for the remote seat when it wasn't their turn
+    OutOfTurn,
+    /// Connecting to a server needs exactly one remote seat
+    RemoteSeats,
+    /// Connecting to a server needs a match with the default rules that hasn't started yet
+    NotAtStart,
+    Illegal(IllegalPlay),
+    /// A bot couldn't make its play
+    Bot(PlayError),
+    /// The server couldn't be reached, or rejected a play made here, which is then not made
+    Network(NetworkGameError),
+}
+
+impl fmt::Display for MatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MatchError::BotsTurn => write!(f, "It is a bot's turn to play"),
+            MatchError::RemotesTurn => write!(f, "It is the remote seat's turn to play"),
+            MatchError::OutOfTurn => write!(f, "The server sent a play out of turn"),
+            MatchError::RemoteSeats => write!(f, "The match needs exactly one remote seat"),
+            MatchError::NotAtStart => write!(
+                f,
+                "Only matches with the default rules can connect, before any plays are made"
+            ),
+            MatchError::Illegal(reason) => write!(f, "Play is illegal: {}", reason),
+            MatchError::Bot(error) => write!(f, "The bot couldn't play: {}", error),
+            MatchError::Network(error) => error.fmt(f),
+        }
+    }
+}
+
+impl From<PlayError> for MatchError {
+    fn from(error: PlayError) -> Self {
+        MatchError::Bot(error)
+    }
+}
+
+impl From<InvalidPlayError> for MatchError {
+    fn from(error: InvalidPlayError) -> Self {
+        match error {
+            InvalidPlayError::Illegal(reason) => MatchError::Illegal(reason),
+        }
+    }
+}
@@ -0,0 +1,435 @@
+use crate::record::{Comment, GameRecord, Start, Variation};
+use crate::state::{GameState, IllegalPlay, Play};
+
+/// A tree of plays branching out from a starting position, for studying a game by trying
+/// alternatives from earlier positions while keeping every line. The first child of each node
+/// continues the main line and the others are variations.
+#[derive(Clone, Debug)]
+pub struct GameTree {
+    /// Deleted nodes leave a gap so the ids of the others stay the same
+    nodes: Vec<Option<Node>>,
+    current: NodeId,
+}
+
+/// Identifies a node in a game tree
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct NodeId(pub(crate) usize);
+
+#[derive(Clone, Debug)]
+struct Node {
+    /// The play made to reach this node, which is None for the root
+    play: Option<Play>,
+    /// The state after the play
+    state: GameState,
+    comment: String,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, uniffi::Enum)]
+pub enum TreeError {
+    /// The node doesn't exist, or was deleted
+    NoNode,
+    /// The root can't be deleted or promoted
+    Root,
+    /// A play in the line couldn't be made
+    Illegal(IllegalPlay),
+    /// The saved tree couldn't be loaded
+    Deserialize(String),
+}
+
+impl GameTree {
+    /// Creates a tree with only the starting position, which is the current node
+    pub fn new(start: GameState) -> Self {
+        GameTree {
+            nodes: vec![Some(Node {
+                play: None,
+                state: start,
+                comment: String::new(),
+                parent: None,
+                children: vec![],
+            })],
+            current: NodeId(0),
+        }
+    }
+
+    /// Recreates the tree saved in the record, played from the position saved with it, with
+    /// the end of the main line as the current node. Records saved without their starting
+    /// position are played from the start given instead.
+    pub fn from_record(start: GameState, record: &GameRecord) -> Result<Self, TreeError> {
+        let start = match &record.start {
+            Some(saved) => saved
+                .game_state()
+                .map_err(|error| TreeError::Deserialize(error.to_string()))?,
+            None => start,
+        };
+        let mut tree = GameTree::new(start);
+        let end = tree.add_line(
+            tree.root(),
+            &record.plays,
+            &record.comments,
+            &record.variations,
+        )?;
+        tree.current = end;
+        Ok(tree)
+    }
+
+    /// Loads a tree saved as JSON by `save`, played from the position saved with it, or from
+    /// the start given if there isn't one.
+    pub fn load(start: GameState, json: &str) -> Result<Self, TreeError> {
+        let record = serde_json::from_str(json)
+            .map_err(|error: serde_json::Error| TreeError::Deserialize(error.to_string()))?;
+        GameTree::from_record(start, &record)
+    }
+
+    /// Saves the tree as JSON in the record format, to be loaded later
+    pub fn save(&self) -> String {
+        serde_json::to_string(&self.to_record()).expect("Game records should always serialize")
+    }
+
+    /// Saves the tree as a record of its main line, with every other line as a variation.
+    pub fn to_record(&self) -> GameRecord {
+        let (plays, comments, variations) = self.record_line(self.root());
+        let end = self.main_line_end(self.root());
+        GameRecord {
+            start: Some(Start::new(self.state(self.root()))),
+            plays,
+            winner: self.state(end).winner(),
+            turn_count: self.state(end).turn_count(),
+            comments,
+            variations,
+        }
+    }
+
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    pub fn current(&self) -> NodeId {
+        self.current
+    }
+
+    /// Returns the state at the current node
+    pub fn current_state(&self) -> &GameState {
+        self.state(self.current)
+    }
+
+    /// Makes the node the current one
+    pub fn go_to(&mut self, id: NodeId) -> Result<(), TreeError> {
+        self.get(id)?;
+        self.current = id;
+        Ok(())
+    }
+
+    /// Makes a play from the current node and moves to the resulting node. If the play was
+    /// already tried from here its node is reused, otherwise it starts a new variation, or
+    /// continues the main line if there are no plays from here yet.
+    pub fn play(&mut self, play: &Play) -> Result<NodeId, TreeError> {
+        self.current = self.add_child(self.current, play)?;
+        Ok(self.current)
+    }
+
+    /// Makes the node's line the main line from its parent, moving it ahead of its siblings.
+    pub fn promote(&mut self, id: NodeId) -> Result<(), TreeError> {
+        let parent = self.get(id)?.parent.ok_or(TreeError::Root)?;
+        let siblings = &mut self.node_mut(parent).children;
+        siblings.retain(|&sibling| sibling != id);
+        siblings.insert(0, id);
+        Ok(())
+    }
+
+    /// Deletes the node and every line that continues from it. If the current node is deleted
+    /// the deleted node's parent becomes the current one.
+    pub fn delete(&mut self, id: NodeId) -> Result<(), TreeError> {
+        let parent = self.get(id)?.parent.ok_or(TreeError::Root)?;
+        self.node_mut(parent)
+            .children
+            .retain(|&sibling| sibling != id);
+        let mut deleting = vec![id];
+        while let Some(next) = deleting.pop() {
+            if next == self.current {
+                self.current = parent;
+            }
+            if let Some(node) = self.nodes[next.0].take() {
+                deleting.extend(node.children);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the play made to reach the node, which is None for the root
+    pub fn play_at(&self, id: NodeId) -> Option<&Play> {
+        self.get(id).ok()?.play.as_ref()
+    }
+
+    /// Returns the state after the node's play
+    pub fn state_at(&self, id: NodeId) -> Option<&GameState> {
+        self.get(id).ok().map(|node| &node.state)
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.get(id).ok()?.parent
+    }
+
+    /// Returns the nodes the plays from this node lead to, with the main line first
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        self.get(id)
+            .map(|node| node.children.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn comment(&self, id: NodeId) -> Option<&str> {
+        self.get(id).ok().map(|node| node.comment.as_str())
+    }
+
+    pub fn set_comment(&mut self, id: NodeId, comment: String) -> Result<(), TreeError> {
+        self.get(id)?;
+        self.node_mut(id).comment = comment;
+        Ok(())
+    }
+
+    /// Returns the plays along the main line from the root
+    pub fn main_line(&self) -> Vec<Play> {
+        self.record_line(self.root()).0
+    }
+
+    fn get(&self, id: NodeId) -> Result<&Node, TreeError> {
+        self.nodes
+            .get(id.0)
+            .and_then(|node| node.as_ref())
+            .ok_or(TreeError::NoNode)
+    }
+
+    /// Returns a node known to exist
+    fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        self.nodes[id.0]
+            .as_mut()
+            .expect("Nodes should only be changed once they are known to exist")
+    }
+
+    /// Returns the state at a node known to exist
+    fn state(&self, id: NodeId) -> &GameState {
+        &self
+            .get(id)
+            .expect("Nodes should only be read once they are known to exist")
+            .state
+    }
+
+    /// Returns the child of the node that the play leads to, adding it if it wasn't tried yet
+    fn add_child(&mut self, parent: NodeId, play: &Play) -> Result<NodeId, TreeError> {
+        let existing = self
+            .get(parent)?
+            .children
+            .iter()
+            .find(|&&child| self.play_at(child) == Some(play));
+        if let Some(&child) = existing {
+            return Ok(child);
+        }
+        let mut state = self.state(parent).clone();
+        state.make_play(play).map_err(TreeError::Illegal)?;
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Some(Node {
+            play: Some(play.clone()),
+            state,
+            comment: String::new(),
+            parent: Some(parent),
+            children: vec![],
+        }));
+        self.node_mut(parent).children.push(id);
+        Ok(id)
+    }
+
+    /// Adds a recorded line of plays from the node, with its comments and variations, and
+    /// returns the node at the end of the line.
+    fn add_line(
+        &mut self,
+        from: NodeId,
+        plays: &[Play],
+        comments: &[Comment],
+        variations: &[Variation],
+    ) -> Result<NodeId, TreeError> {
+        // The node reached after each number of plays into the line
+        let mut line = vec![from];
+        for play in plays {
+            line.push(self.add_child(line[line.len() - 1], play)?);
+        }
+        for comment in comments {
+            let id = *line.get(comment.after).ok_or(TreeError::NoNode)?;
+            self.node_mut(id).comment = comment.text.clone();
+        }
+        for variation in variations {
+            let id = *line.get(variation.at).ok_or(TreeError::NoNode)?;
+            self.add_line(
+                id,
+                &variation.plays,
+                &variation.comments,
+                &variation.variations,
+            )?;
+        }
+        Ok(line[line.len() - 1])
+    }
+
+    /// Returns the last node reached by following the main line from the node
+    fn main_line_end(&self, mut id: NodeId) -> NodeId {
+        while let Some(&next) = self.children(id).first() {
+            id = next;
+        }
+        id
+    }
+
+    /// Records the line that starts with the node's play and follows the main line from there,
+    /// with the comments on it and the variations branching off it.
+    fn record_line(&self, mut id: NodeId) -> (Vec<Play>, Vec<Comment>, Vec<Variation>) {
+        let mut plays = Vec::new();
+        let mut comments = Vec::new();
+        let mut variations = Vec::new();
+        loop {
+            plays.extend(self.play_at(id).cloned());
+            if let Some(text) = self.comment(id).filter(|text| !text.is_empty()) {
+                comments.push(Comment {
+                    after: plays.len(),
+                    text: text.to_string(),
+                });
+            }
+            let Some((&main, alternatives)) = self.children(id).split_first() else {
+                break;
+            };
+            for &alternative in alternatives {
+                let (line, line_comments, line_variations) = self.record_line(alternative);
+                variations.push(Variation {
+                    at: plays.len(),
+                    plays: line,
+                    comments: line_comments,
+                    variations: line_variations,
+                });
+            }
+            id = main;
+        }
+        (plays, comments, variations)
+    }
+}
+
+impl std::fmt::Display for TreeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            TreeError::NoNode => write!(f, "There is no such node in the tree"),
+            TreeError::Root => write!(f, "The root of the tree can't be changed"),
+            TreeError::Illegal(reason) => write!(f, "Play is illegal: {}", reason),
+            TreeError::Deserialize(error) => write!(f, "Unable to load game tree: {}", error),
+        }
+    }
+}
+
+#[test]
+fn variations_branch_and_can_be_promoted_or_deleted() {
+    let play = |from, to| Play { from, to };
+    let mut tree = GameTree::new(GameState::default());
+    let first = tree.play(&play((3, 5), (3, 4))).unwrap();
+    let main = tree.play(&play((0, 3), (1, 3))).unwrap();
+    tree.go_to(first).unwrap();
+    let alternative = tree.play(&play((0, 4), (1, 4))).unwrap();
+    // Playing a play that was already tried reuses its node
+    tree.go_to(first).unwrap();
+    assert_eq!(Ok(main), tree.play(&play((0, 3), (1, 3))));
+    assert_eq!(&[main, alternative], tree.children(first));
+    assert_eq!(
+        Err(TreeError::Illegal(IllegalPlay::OpponentsPiece)),
+        tree.play(&play((5, 1), (5, 0)))
+    );
+
+    assert_eq!(
+        vec![play((3, 5), (3, 4)), play((0, 3), (1, 3))],
+        tree.main_line()
+    );
+    tree.promote(alternative).unwrap();
+    assert_eq!(
+        vec![play((3, 5), (3, 4)), play((0, 4), (1, 4))],
+        tree.main_line()
+    );
+    assert_eq!(Err(TreeError::Root), tree.promote(tree.root()));
+
+    tree.go_to(main).unwrap();
+    tree.delete(first).unwrap();
+    assert_eq!(tree.root(), tree.current());
+    assert!(tree.main_line().is_empty());
+    assert_eq!(Err(TreeError::NoNode), tree.go_to(alternative));
+}
+
+#[test]
+fn game_trees_are_saved_in_the_record_format() {
+    let play = |from, to| Play { from, to };
+    let mut tree = GameTree::new(GameState::default());
+    tree.set_comment(tree.root(), "A quiet start".to_string())
+        .unwrap();
+    let first = tree.play(&play((3, 5), (3, 4))).unwrap();
+    let main = tree.play(&play((0, 3), (1, 3))).unwrap();
+    tree.play(&play((3, 4), (3, 5))).unwrap();
+    tree.go_to(first).unwrap();
+    let alternative = tree.play(&play((0, 4), (1, 4))).unwrap();
+    tree.set_comment(alternative, "Also fine".to_string())
+        .unwrap();
+    tree.go_to(main).unwrap();
+    tree.play(&play((3, 4), (2, 4))).unwrap();
+
+    let record = tree.to_record();
+    assert_eq!(3, record.plays.len());
+    assert_eq!(3, record.turn_count);
+    assert_eq!(
+        vec![Comment {
+            after: 0,
+            text: "A quiet start".to_string(),
+        }],
+        record.comments
+    );
+    assert_eq!(2, record.variations.len());
+    assert_eq!(1, record.variations[0].at);
+    assert_eq!(
+        vec![Comment {
+            after: 1,
+            text: "Also fine".to_string(),
+        }],
+        record.variations[0].comments
+    );
+    assert_eq!(2, record.variations[1].at);
+
+    let loaded = GameTree::load(GameState::default(), &tree.save()).unwrap();
+    assert_eq!(record, loaded.to_record());
+    assert!(matches!(
+        GameTree::load(GameState::default(), "{"),
+        Err(TreeError::Deserialize(_))
+    ));
+    assert_eq!(3, loaded.current_state().turn_count());
+}
+
+#[test]
+fn game_trees_load_from_the_position_they_were_saved_with() {
+    use crate::rules::Rules;
+
+    let mut game_state = GameState::new(Rules::copenhagen());
+    game_state
+        .make_play(&Play {
+            from: (3, 0),
+            to: (3, 2),
+        })
+        .unwrap();
+    let mut tree = GameTree::new(game_state.clone());
+    let play = Play {
+        from: (5, 3),
+        to: (2, 3),
+    };
+    tree.play(&play).unwrap();
+    let loaded = GameTree::load(GameState::default(), &tree.save()).unwrap();
+    assert_eq!(vec![play], loaded.main_line());
+    let root = loaded.state_at(loaded.root()).unwrap();
+    assert_eq!(game_state.pieces(), root.pieces());
+    assert_eq!(Rules::copenhagen(), root.rules());
+
+    let mut record = tree.to_record();
+    if let Some(start) = &mut record.start {
+        start.tiles.truncate(100);
+    }
+    assert!(matches!(
+        GameTree::from_record(GameState::default(), &record),
+        Err(TreeError::Deserialize(_))
+    ));
+}
@@ -1,12 +1,21 @@
 // Only want these allowed inside function bodies but no way to configure the lint for that :(
 #![allow(mixed_script_confusables)]
 
-use uniffi;
-
 uniffi::setup_scaffolding!();
 
-mod bot;
+mod clock;
 mod config;
 mod ffi;
-mod piece;
-mod state;
+
+// Shared with the CLI binary
+pub mod bot;
+pub mod editor;
+pub mod game_tree;
+pub mod network;
+pub mod piece;
+pub mod record;
+pub mod rules;
+pub mod series;
+pub mod session;
+pub mod state;
+pub mod threats;
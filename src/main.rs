@@ -1,15 +1,25 @@
 // Only want these allowed inside function bodies but no way to configure the lint for that :(
 #![allow(mixed_script_confusables)]
 
-uniffi::setup_scaffolding!();
+mod engine;
+mod tournament;
 
-mod bot;
-mod piece;
-mod state;
+use hnefatafl::{bot, network, piece, record, rules, series, session, state};
 
-use state::{GameState, GameStateUpdate, Play, Player};
+use bot::{Bot, Strategy};
+use engine::Engine;
+use network::server::Server;
+use rules::Rules;
+use series::{Participant, Series};
+use session::{Seat, Seats};
+use state::{EndReason, GameState, GameStateUpdate, Play, Player};
+use tournament::Tournament;
 
+use std::fmt::Display;
+use std::fs;
+use std::io;
 use std::num::ParseIntError;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use clap::{Parser, Subcommand};
@@ -27,46 +37,235 @@ struct Arguments {
 enum Mode {
     /// Two player game
     TwoPlayer,
+    /// Bot vs bot games, alternating which bot plays which side
+    Tournament {
+        /// Strategy for the first bot, which plays Red in the first game (min-max or random)
+        #[arg(long, default_value_t = Strategy::MinMax)]
+        first: Strategy,
+        /// Strategy for the second bot, which plays White in the first game (min-max or random)
+        #[arg(long, default_value_t = Strategy::Random)]
+        second: Strategy,
+        /// Number of games to play
+        #[arg(long, default_value_t = 10)]
+        games: u32,
+        /// Number of random plays to start each pair of games with
+        #[arg(long, default_value_t = 0)]
+        random_openings: u32,
+        /// Number of turns after which a game is called a draw
+        #[arg(long, default_value_t = 500)]
+        max_turns: u32,
+        /// Directory to save the finished games to
+        #[arg(long, default_value = "tournament")]
+        output: PathBuf,
+    },
+    /// Game against a bot
+    PlayBot {
+        /// Side to play as (attackers or defenders)
+        #[arg(long, default_value_t = Side::Defenders)]
+        side: Side,
+        /// Strategy for the bot (min-max or random)
+        #[arg(long, default_value_t = Strategy::MinMax)]
+        strategy: Strategy,
+    },
+    /// Series of games where the two players swap sides after every game
+    Series {
+        /// Who plays Red in the first game (human, min-max or random)
+        #[arg(long, default_value_t = Contender::Human)]
+        first: Contender,
+        /// Who plays White in the first game (human, min-max or random)
+        #[arg(long, default_value_t = Contender::Bot(Strategy::MinMax))]
+        second: Contender,
+        /// Number of games to play, 2 by default. A resumed series keeps its own number.
+        #[arg(long)]
+        games: Option<u32>,
+        /// Number of turns after which a game is called a draw
+        #[arg(long, default_value_t = 500)]
+        max_turns: u32,
+        /// File to save the series to after every game, which is resumed if it already exists
+        #[arg(long)]
+        save: Option<PathBuf>,
+    },
+    /// Engine speaking the OpenTafl engine protocol over stdin and stdout
+    Engine,
+    /// Server for network games
+    Serve {
+        /// Address to listen for connections on
+        #[arg(long, default_value = "0.0.0.0:7878")]
+        address: String,
+    },
 }
 
-impl Play {
-    fn new(play: Move) -> Self {
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// Side a human plays as
+enum Side {
+    Attackers,
+    Defenders,
+}
+
+impl From<Side> for Player {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::Attackers => Player::Attacker,
+            Side::Defenders => Player::Defender,
+        }
+    }
+}
+
+impl FromStr for Side {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "attackers" => Ok(Side::Attackers),
+            "defenders" => Ok(Side::Defenders),
+            _ => Err(format!(
+                "Unknown side {}, expected attackers or defenders",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "{}",
+            match self {
+                Side::Attackers => "attackers",
+                Side::Defenders => "defenders",
+            }
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+/// Who plays for one participant in a series
+enum Contender {
+    Human,
+    Bot(Strategy),
+}
+
+impl Contender {
+    fn seat(&self) -> Seat {
+        match self {
+            Contender::Human => Seat::Human,
+            Contender::Bot(strategy) => Seat::Bot(Bot::new(*strategy)),
+        }
+    }
+}
+
+impl FromStr for Contender {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(Contender::Human),
+            _ => s
+                .parse()
+                .map(Contender::Bot)
+                .map_err(|_| format!("Unknown player {}, expected human, min-max or random", s)),
+        }
+    }
+}
+
+impl Display for Contender {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            Contender::Human => write!(f, "human"),
+            Contender::Bot(strategy) => write!(f, "{}", strategy),
+        }
+    }
+}
+
+impl From<Move> for Play {
+    fn from(play: Move) -> Self {
         Play {
-            from: (play.from.x.into(), play.from.y.into()),
-            to: (play.to.x.into(), play.to.y.into()),
+            from: (play.from.x, play.from.y),
+            to: (play.to.x, play.to.y),
         }
     }
 }
 
 fn make_play(game: &mut GameState, play: Play) {
     match game.make_play(&play) {
-        Ok(info) => {
-            println!("{}", game);
-            match info {
-                GameStateUpdate::DefenderWin => println!("White wins!"),
-                GameStateUpdate::AttackerWin => println!("Red wins!"),
-                GameStateUpdate::DefenderCapture => println!("Capture!"),
-                GameStateUpdate::AttackerCapture => println!("Capture!"),
-                GameStateUpdate::Nothing => (),
-            }
-            println!("");
-        }
-        Err(_) => println!("Invalid move"),
+        Ok(info) => announce_play(game, info),
+        Err(reason) => println!("Invalid move: {}", reason),
+    }
+}
+
+/// Prints the board after a play, and what the play did.
+fn announce_play(game: &GameState, info: GameStateUpdate) {
+    println!("{}", game);
+    match info {
+        GameStateUpdate::DefenderWin => println!("White wins!"),
+        GameStateUpdate::AttackerWin => println!("Red wins!"),
+        GameStateUpdate::DefenderWinOnTime => println!("Red ran out of time, White wins!"),
+        GameStateUpdate::AttackerWinOnTime => println!("White ran out of time, Red wins!"),
+        GameStateUpdate::DefenderCapture => println!("Capture!"),
+        GameStateUpdate::AttackerCapture => println!("Capture!"),
+        GameStateUpdate::DefenderShieldwallCapture => println!("Shieldwall captured!"),
+        GameStateUpdate::AttackerShieldwallCapture => println!("Shieldwall captured!"),
+        GameStateUpdate::Draw => println!("Draw!"),
+        GameStateUpdate::Nothing => (),
     }
+    println!();
 }
 
 fn main() {
     let arguments = Arguments::parse();
     match arguments.mode {
         Mode::TwoPlayer => two_player(),
+        Mode::PlayBot { side, strategy } => play_bot(side, strategy),
+        Mode::Series {
+            first,
+            second,
+            games,
+            max_turns,
+            save,
+        } => play_series(first, second, games, max_turns, save),
+        Mode::Engine => {
+            if let Err(error) = Engine::new().run() {
+                eprintln!("Error running engine: {}", error);
+            }
+        }
+        Mode::Serve { address } => {
+            let result = Server::bind(address).and_then(|server| {
+                println!("Listening on {}", server.local_addr()?);
+                server.run()
+            });
+            if let Err(error) = result {
+                eprintln!("Error running server: {}", error);
+            }
+        }
+        Mode::Tournament {
+            first,
+            second,
+            games,
+            random_openings,
+            max_turns,
+            output,
+        } => {
+            let tournament = Tournament {
+                first,
+                second,
+                games,
+                random_openings,
+                max_turns,
+                output,
+            };
+            if let Err(error) = tournament.run() {
+                eprintln!("Error running tournament: {}", error);
+            }
+        }
     }
 }
 
 #[derive(Debug, PartialEq)]
 enum ParseMoveError {
-    ParseIntError(ParseIntError),
-    ParsePositionError,
-    ParseMoveError,
+    Int(ParseIntError),
+    Position,
+    Move,
 }
 
 #[derive(Debug, PartialEq)]
@@ -83,7 +282,7 @@ struct Position {
 
 impl From<ParseIntError> for ParseMoveError {
     fn from(error: ParseIntError) -> Self {
-        ParseMoveError::ParseIntError(error)
+        ParseMoveError::Int(error)
     }
 }
 
@@ -97,7 +296,7 @@ impl FromStr for Position {
             .collect();
 
         if coords.len() != 2 {
-            return Err(ParseMoveError::ParsePositionError);
+            return Err(ParseMoveError::Position);
         }
 
         Ok(Position {
@@ -114,7 +313,7 @@ impl FromStr for Move {
         let (from, to) = s
             .split_once("->")
             .or_else(|| s.split_once("to"))
-            .ok_or(ParseMoveError::ParseMoveError)?;
+            .ok_or(ParseMoveError::Move)?;
 
         Ok(Move {
             from: from.trim().parse()?,
@@ -128,41 +327,248 @@ fn two_player() {
     let mut rl = rustyline::Editor::<()>::new();
     println!("{}\n", game);
     println!("Enter 'enumerate' to list available moves");
+    println!("Enter 'resign', 'offer draw', 'accept draw' or 'decline draw' to end the game early");
     loop {
         //println!("NN encoding: {:?}", game.representation());
-        let input = match rl.readline("Enter move: ") {
-            Ok(s) => s,
-            Err(_) => return,
-        };
-        if input.trim() == "enumerate" {
-            println!("Available moves:\n");
-            let plays = game.available_plays();
-            let total = plays.len();
-            for (i, play) in plays.iter().enumerate() {
-                let formatted = play.to_string();
-                print!("{} ", formatted);
-                for _ in 0.."(10,10) -> (10,11)".len() - formatted.len() {
-                    print!(" ");
-                }
-                if i % 4 == 3 || i == total {
-                    println!();
+        if !human_turn(&mut rl, &mut game) {
+            return;
+        }
+        if announce_winner(&game) {
+            return;
+        }
+    }
+}
+
+fn play_bot(side: Side, strategy: Strategy) {
+    let human: Player = side.into();
+    let bot = Seat::Bot(Bot::new(strategy));
+    let seats = match human {
+        Player::Attacker => Seats {
+            attackers: Seat::Human,
+            defenders: bot,
+        },
+        Player::Defender => Seats {
+            attackers: bot,
+            defenders: Seat::Human,
+        },
+    };
+    let mut game = GameState::default();
+    let mut rl = rustyline::Editor::<()>::new();
+    println!("{}\n", game);
+    println!("Playing as {} against the {} bot", human, strategy);
+    println!("Enter 'enumerate' to list available moves");
+    println!("Enter 'resign', 'offer draw', 'accept draw' or 'decline draw' to end the game early");
+    loop {
+        if seats.bot_to_move(&game).is_some() {
+            if game.draw_offer() == Some(human) && game.decline_draw(human.next()).is_ok() {
+                println!("The bot declines the draw offer");
+            }
+            match seats.step(&mut game) {
+                Some((play, info)) => {
+                    println!("Bot plays {}", play);
+                    announce_play(&game, info);
                 }
+                None => return,
             }
-            continue;
+        } else if !human_turn(&mut rl, &mut game) {
+            return;
         }
-        let player_move = input.trim().parse::<Move>();
-        match player_move {
-            Ok(m) => make_play(&mut game, Play::new(m)),
-            Err(_) => {
-                println!("Did not understand input, expected input in the form (0, 0) -> (5, 0)")
+        if announce_winner(&game) {
+            return;
+        }
+    }
+}
+
+fn play_series(
+    first: Contender,
+    second: Contender,
+    games: Option<u32>,
+    max_turns: u32,
+    save: Option<PathBuf>,
+) {
+    let mut series = match save.as_ref().filter(|path| path.exists()) {
+        Some(path) => match load_series(path) {
+            Ok(series) => {
+                if let Some(games) = games.filter(|&games| games != series.games) {
+                    eprintln!(
+                        "The series saved in {} has {} games, so can't be resumed with {}",
+                        path.display(),
+                        series.games,
+                        games
+                    );
+                    return;
+                }
+                println!("Resuming the series saved in {}", path.display());
+                series
             }
+            Err(error) => {
+                eprintln!("Error loading series: {}", error);
+                return;
+            }
+        },
+        None => Series::new(games.unwrap_or(2), Rules::default()),
+    };
+    let mut rl = rustyline::Editor::<()>::new();
+    while let Some(index) = series.current_game() {
+        let red = match Series::side(Participant::First, index) {
+            Player::Attacker => Participant::First,
+            Player::Defender => Participant::Second,
         };
-        if let Some(winner) = game.winner() {
-            match winner {
-                Player::Attacker => println!("The King was captured!"),
-                Player::Defender => println!("The King escapes!"),
-            };
-            return;
+        let contender = |participant| match participant {
+            Participant::First => first,
+            Participant::Second => second,
+        };
+        let seats = Seats {
+            attackers: contender(red).seat(),
+            defenders: contender(red.other()).seat(),
+        };
+        println!(
+            "Game {} of {}: {} ({}) plays Red and {} ({}) plays White",
+            index + 1,
+            series.games,
+            red,
+            contender(red),
+            red.other(),
+            contender(red.other()),
+        );
+        let mut game = series.new_game();
+        let mut plays = Vec::new();
+        println!("{}\n", game);
+        while !game.is_over() && game.turn_count() < max_turns {
+            let last_play = game.last_play().cloned();
+            if let Some((play, info)) = seats.step(&mut game) {
+                println!("Bot plays {}", play);
+                announce_play(&game, info);
+            } else if !human_turn(&mut rl, &mut game) {
+                return;
+            }
+            if game.last_play() != last_play.as_ref() {
+                plays.extend(game.last_play().cloned());
+            }
+        }
+        if !game.is_over() {
+            game.reach_turn_limit()
+                .expect("The game can't be over while it is still being played");
         }
+        announce_winner(&game);
+        series
+            .record_game(plays, &game)
+            .expect("Games played in the series should always be recorded");
+        if let Some(path) = &save {
+            if let Err(error) = save_series(path, &series) {
+                eprintln!("Error saving series: {}", error);
+            }
+        }
+        announce_standings(&series);
+    }
+}
+
+/// Prints the score of each participant in the series, and who is ahead.
+fn announce_standings(series: &Series) {
+    for participant in [Participant::First, Participant::Second] {
+        let standing = series.standing(participant);
+        println!(
+            "{}: {} wins, {} losses, {} draws",
+            participant, standing.wins, standing.losses, standing.draws
+        );
+    }
+    match series.leader() {
+        Some(leader) if series.is_over() => println!("{} wins the series!", leader),
+        Some(leader) => println!("{} leads the series", leader),
+        None => println!("The series is level"),
+    }
+}
+
+fn load_series(path: &Path) -> io::Result<Series> {
+    Series::load(&fs::read_to_string(path)?)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error.to_string()))
+}
+
+fn save_series(path: &Path, series: &Series) -> io::Result<()> {
+    fs::write(path, series.save())
+}
+
+/// Reads and makes a play for the turn player, returning false if there is no more input.
+fn human_turn(rl: &mut rustyline::Editor<()>, game: &mut GameState) -> bool {
+    if game.draw_offer() == Some(game.turn().next()) {
+        println!("{} offers a draw", game.turn().next());
+    }
+    let input = match rl.readline("Enter move: ") {
+        Ok(s) => s,
+        Err(_) => return false,
+    };
+    if input.trim() == "enumerate" {
+        println!("Available moves:\n");
+        let plays = game.available_plays();
+        let total = plays.len();
+        for (i, play) in plays.iter().enumerate() {
+            let formatted = play.to_string();
+            print!("{} ", formatted);
+            for _ in 0.."(10,10) -> (10,11)".len() - formatted.len() {
+                print!(" ");
+            }
+            if i % 4 == 3 || i == total {
+                println!();
+            }
+        }
+        return true;
+    }
+    if take_action(game, input.trim()) {
+        return true;
+    }
+    let player_move = input.trim().parse::<Move>();
+    match player_move {
+        Ok(m) => make_play(game, Play::from(m)),
+        Err(_) => {
+            println!("Did not understand input, expected input in the form (0, 0) -> (5, 0)")
+        }
+    };
+    true
+}
+
+/// Carries out a resignation or draw command for the turn player, returning false if the
+/// input wasn't one.
+fn take_action(game: &mut GameState, input: &str) -> bool {
+    let player = game.turn();
+    let result = match input {
+        "resign" => game.resign(player).map(|_| ()),
+        "offer draw" => game.offer_draw(player),
+        "accept draw" => game.accept_draw(player).map(|_| ()),
+        "decline draw" => game.decline_draw(player),
+        _ => return false,
+    };
+    if let Err(reason) = result {
+        println!("Can't {}: {}", input, reason);
+    }
+    true
+}
+
+/// Prints how the game was won, returning true if it is over.
+fn announce_winner(game: &GameState) -> bool {
+    let result = match game.result() {
+        Some(result) => result,
+        None => return false,
+    };
+    let loser = result.winner.map(|winner| winner.next());
+    match (result.reason, loser) {
+        (EndReason::KingCaptured, _) => println!("The King was captured!"),
+        (EndReason::KingEscaped, _) => println!("The King escapes!"),
+        (EndReason::EdgeFort, _) => println!("The King is safe in an edge fort!"),
+        (EndReason::Encircled, _) => println!("The defenders are surrounded!"),
+        (EndReason::NoPlays, Some(loser)) => println!("{} has no moves left!", loser),
+        (EndReason::NoPlays, None) => println!("Nobody can move."),
+        (EndReason::Time, Some(loser)) => println!("{} ran out of time!", loser),
+        (EndReason::Time, None) => println!("Time ran out."),
+        (EndReason::Resignation, Some(loser)) => println!("{} resigns!", loser),
+        (EndReason::Resignation, None) => println!("The game was resigned."),
+        (EndReason::Agreement, _) => println!("The players agree to a draw."),
+        (EndReason::Repetition, _) => println!("The same position came up three times!"),
+        (EndReason::TurnLimit, _) => println!("The game reached the turn limit."),
+    };
+    match result.winner {
+        Some(winner) if result.reason == EndReason::Resignation => println!("{} wins!", winner),
+        Some(_) => (),
+        None => println!("The game is drawn."),
     }
+    true
 }
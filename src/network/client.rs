@@ -0,0 +1,277 @@
+use crate::network::{send, ClientMessage, NetworkError, ServerMessage};
+use crate::state::{GameState, Play, Player};
+
+use std::io::{BufRead, BufReader};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+/// A connection to a seat in a game on a server.
+///
+/// The client keeps a copy of the game state which is updated in the background as the server
+/// sends plays made by either seat.
+#[derive(Debug)]
+pub struct Client {
+    writer: Mutex<TcpStream>,
+    shared: Arc<Mutex<ClientState>>,
+    /// Signalled whenever the shared state changes
+    changed: Arc<Condvar>,
+    game: String,
+    seat: Player,
+    token: String,
+}
+
+/// The client's view of the game, as last sent by the server
+#[derive(Clone, Debug)]
+pub struct ClientState {
+    pub game_state: GameState,
+    pub plays: Vec<Play>,
+    /// The reason the server gave for rejecting our last message, if it did
+    pub rejection: Option<String>,
+    pub connected: bool,
+}
+
+impl Client {
+    /// Connects to the server and takes a seat in the game, reclaiming it if given the token
+    /// from an earlier connection.
+    pub fn connect(
+        address: impl ToSocketAddrs,
+        game: &str,
+        seat: Player,
+        token: Option<String>,
+    ) -> Result<Client, NetworkError> {
+        let mut writer = TcpStream::connect(address)?;
+        let mut reader = BufReader::new(writer.try_clone()?);
+        send(
+            &mut writer,
+            &ClientMessage::Join {
+                game: game.to_string(),
+                seat,
+                token,
+            },
+        )?;
+        let token = match read(&mut reader)? {
+            Some(ServerMessage::Joined { token, .. }) => token,
+            Some(ServerMessage::Rejected { reason }) => return Err(NetworkError::Rejected(reason)),
+            _ => return Err(NetworkError::Disconnected),
+        };
+        let shared = Arc::new(Mutex::new(ClientState {
+            game_state: GameState::default(),
+            plays: vec![],
+            rejection: None,
+            connected: true,
+        }));
+        let changed = Arc::new(Condvar::new());
+        let background = shared.clone();
+        let signal = changed.clone();
+        thread::spawn(move || {
+            while let Ok(Some(message)) = read(&mut reader) {
+                let mut state = background
+                    .lock()
+                    .expect("Poisoned mutex in Client background");
+                state.update(message);
+                signal.notify_all();
+            }
+            background
+                .lock()
+                .expect("Poisoned mutex in Client background")
+                .connected = false;
+            signal.notify_all();
+        });
+        Ok(Client {
+            writer: Mutex::new(writer),
+            shared,
+            changed,
+            game: game.to_string(),
+            seat,
+            token,
+        })
+    }
+
+    /// Sends a play to the server. The play only takes effect once the server has validated
+    /// it and sent it back, which will show up in the state.
+    pub fn make_play(&self, play: Play) -> Result<(), NetworkError> {
+        if !self.state().connected {
+            return Err(NetworkError::Disconnected);
+        }
+        let mut writer = self
+            .writer
+            .lock()
+            .expect("Poisoned mutex in Client make_play");
+        send(&mut *writer, &ClientMessage::Play { play })?;
+        Ok(())
+    }
+
+    /// Sends a play to the server and waits for its answer, returning once the server has
+    /// made the play or with the reason it was rejected.
+    pub fn make_play_and_wait(&self, play: Play) -> Result<(), NetworkError> {
+        let seen = {
+            let mut state = self
+                .shared
+                .lock()
+                .expect("Poisoned mutex in Client make_play_and_wait");
+            // Only a rejection of this play counts
+            state.rejection = None;
+            state.plays.len()
+        };
+        self.make_play(play)?;
+        let mut state = self
+            .shared
+            .lock()
+            .expect("Poisoned mutex in Client make_play_and_wait");
+        loop {
+            if state.plays.len() > seen {
+                return Ok(());
+            }
+            if let Some(reason) = &state.rejection {
+                return Err(NetworkError::Rejected(reason.clone()));
+            }
+            if !state.connected {
+                return Err(NetworkError::Disconnected);
+            }
+            state = self
+                .changed
+                .wait(state)
+                .expect("Poisoned mutex in Client make_play_and_wait");
+        }
+    }
+
+    /// Returns a copy of the latest state
+    pub fn state(&self) -> ClientState {
+        self.shared
+            .lock()
+            .expect("Poisoned mutex in Client state")
+            .clone()
+    }
+
+    /// Waits until the server has sent more than the number of plays already seen, returning
+    /// every play so far, or None once the connection has closed without any more arriving.
+    pub fn wait_for_plays(&self, seen: usize) -> Option<Vec<Play>> {
+        let mut state = self
+            .shared
+            .lock()
+            .expect("Poisoned mutex in Client wait_for_plays");
+        loop {
+            if state.plays.len() > seen {
+                return Some(state.plays.clone());
+            }
+            if !state.connected {
+                return None;
+            }
+            state = self
+                .changed
+                .wait(state)
+                .expect("Poisoned mutex in Client wait_for_plays");
+        }
+    }
+
+    pub fn game(&self) -> &str {
+        &self.game
+    }
+
+    pub fn seat(&self) -> Player {
+        self.seat
+    }
+
+    /// Returns the token needed to reclaim this seat after a disconnect
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        if let Ok(writer) = self.writer.lock() {
+            // Also stops the background thread, as its reads will fail
+            let _ = writer.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+impl ClientState {
+    fn update(&mut self, message: ServerMessage) {
+        match message {
+            ServerMessage::State { plays } => {
+                let mut game_state = GameState::default();
+                for play in &plays {
+                    if game_state.make_play(play).is_err() {
+                        eprintln!("Server sent an illegal play {}", play);
+                    }
+                }
+                self.game_state = game_state;
+                self.plays = plays;
+            }
+            ServerMessage::Played { play, .. } => {
+                if self.game_state.make_play(&play).is_err() {
+                    eprintln!("Server sent an illegal play {}", play);
+                }
+                self.plays.push(play);
+                self.rejection = None;
+            }
+            ServerMessage::Rejected { reason } => self.rejection = Some(reason),
+            ServerMessage::Joined { .. } => (),
+        }
+    }
+}
+
+fn read(reader: &mut BufReader<TcpStream>) -> Result<Option<ServerMessage>, NetworkError> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    serde_json::from_str(&line)
+        .map(Some)
+        .map_err(|error| NetworkError::Io(error.into()))
+}
+
+#[cfg(test)]
+fn wait_for(client: &Client, condition: impl Fn(&ClientState) -> bool) -> ClientState {
+    for _ in 0..500 {
+        let state = client.state();
+        if condition(&state) {
+            return state;
+        }
+        thread::sleep(std::time::Duration::from_millis(10));
+    }
+    panic!("Timed out waiting for the server");
+}
+
+#[test]
+fn two_clients_play_over_localhost() {
+    use crate::network::server::Server;
+
+    let server = Server::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    thread::spawn(move || server.run());
+
+    let defender = Client::connect(address, "test", Player::Defender, None).unwrap();
+    let attacker = Client::connect(address, "test", Player::Attacker, None).unwrap();
+    assert!(matches!(
+        Client::connect(address, "test", Player::Attacker, None),
+        Err(NetworkError::Rejected(_))
+    ));
+
+    assert!(matches!(
+        attacker.make_play_and_wait(Play {
+            from: (3, 0),
+            to: (3, 2),
+        }),
+        Err(NetworkError::Rejected(_))
+    ));
+
+    let play = Play {
+        from: (5, 3),
+        to: (2, 3),
+    };
+    defender.make_play(play.clone()).unwrap();
+    let state = wait_for(&attacker, |state| state.plays.len() == 1);
+    assert_eq!(vec![play.clone()], state.plays);
+    assert_eq!(Player::Attacker, state.game_state.turn());
+
+    // Reconnecting with the token catches up on the plays made so far
+    let token = defender.token().to_string();
+    drop(defender);
+    let defender = Client::connect(address, "test", Player::Defender, Some(token)).unwrap();
+    let state = wait_for(&defender, |state| state.plays.len() == 1);
+    assert_eq!(vec![play], state.plays);
+}
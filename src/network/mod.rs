@@ -0,0 +1,77 @@
+use crate::state::{GameStateUpdate, Play, Player};
+
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+use std::io::{self, Write};
+
+pub mod client;
+pub mod server;
+
+/// Messages sent from a client to the server, one JSON object per line.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum ClientMessage {
+    /// Takes a seat in a game, creating the game if it doesn't exist yet. Giving the token
+    /// from an earlier `Joined` reclaims the seat after a disconnect.
+    Join {
+        game: String,
+        seat: Player,
+        token: Option<String>,
+    },
+    Play {
+        play: Play,
+    },
+}
+
+/// Messages sent from the server to clients, one JSON object per line.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum ServerMessage {
+    Joined {
+        game: String,
+        seat: Player,
+        token: String,
+    },
+    /// Every play made so far in the game, sent on joining so a client can catch up
+    State {
+        plays: Vec<Play>,
+    },
+    /// A play made by either seat, sent to both
+    Played {
+        play: Play,
+        game_state_update: GameStateUpdate,
+    },
+    Rejected {
+        reason: String,
+    },
+}
+
+#[derive(Debug)]
+pub enum NetworkError {
+    Io(io::Error),
+    Rejected(String),
+    Disconnected,
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkError::Io(error) => write!(f, "Network error: {}", error),
+            NetworkError::Rejected(reason) => write!(f, "Rejected by server: {}", reason),
+            NetworkError::Disconnected => write!(f, "Disconnected from server"),
+        }
+    }
+}
+
+impl From<io::Error> for NetworkError {
+    fn from(error: io::Error) -> Self {
+        NetworkError::Io(error)
+    }
+}
+
+fn send<T: Serialize>(writer: &mut impl Write, message: &T) -> io::Result<()> {
+    let mut line = serde_json::to_string(message)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    line.push('\n');
+    writer.write_all(line.as_bytes())?;
+    writer.flush()
+}
@@ -0,0 +1,364 @@
+use crate::network::{send, ClientMessage, ServerMessage};
+use crate::state::{GameState, Play, Player};
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// A game server holding the authoritative state of every game being played on it.
+///
+/// Each connection takes one seat in one game. Plays are validated against the server's
+/// state before being sent to both seats, so clients never need to trust each other. A seat
+/// can be reclaimed with its token after a disconnect, until both seats have left and the
+/// game is dropped. Reclaiming a seat that is still connected closes the old connection, so
+/// only one connection ever plays for a seat.
+#[derive(Debug)]
+pub struct Server {
+    listener: TcpListener,
+    games: Arc<Mutex<HashMap<String, Game>>>,
+    connections: Arc<Mutex<HashMap<u64, TcpStream>>>,
+    stopping: Arc<AtomicBool>,
+}
+
+/// Stops a running server from another thread
+#[derive(Debug)]
+pub struct Stopper {
+    address: SocketAddr,
+    connections: Arc<Mutex<HashMap<u64, TcpStream>>>,
+    stopping: Arc<AtomicBool>,
+}
+
+#[derive(Debug)]
+struct Game {
+    state: GameState,
+    plays: Vec<Play>,
+    seats: HashMap<Player, Seat>,
+}
+
+#[derive(Debug)]
+struct Seat {
+    token: String,
+    /// The id and outbox of the connection currently in this seat, if still connected
+    connection: Option<(u64, Sender<ServerMessage>)>,
+}
+
+static NEXT_CONNECTION: AtomicU64 = AtomicU64::new(0);
+
+impl Server {
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<Server> {
+        Ok(Server {
+            listener: TcpListener::bind(address)?,
+            games: Arc::new(Mutex::new(HashMap::new())),
+            connections: Arc::new(Mutex::new(HashMap::new())),
+            stopping: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Returns a stopper for the server, to be kept before calling `run`
+    pub fn stopper(&self) -> io::Result<Stopper> {
+        Ok(Stopper {
+            address: self.local_addr()?,
+            connections: self.connections.clone(),
+            stopping: self.stopping.clone(),
+        })
+    }
+
+    /// Accepts connections until the server is stopped, handling each on its own thread. A
+    /// connection that fails to be accepted is skipped, so it never takes down the games
+    /// already being played.
+    pub fn run(self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            if self.stopping.load(Ordering::SeqCst) {
+                break;
+            }
+            let (stream, clone) = match stream.and_then(|stream| {
+                let clone = stream.try_clone()?;
+                Ok((stream, clone))
+            }) {
+                Ok(streams) => streams,
+                Err(error) => {
+                    eprintln!("Error accepting connection: {}", error);
+                    continue;
+                }
+            };
+            let games = self.games.clone();
+            let connections = self.connections.clone();
+            let id = NEXT_CONNECTION.fetch_add(1, Ordering::Relaxed);
+            connections
+                .lock()
+                .expect("Poisoned mutex in Server run")
+                .insert(id, clone);
+            thread::spawn(move || {
+                if let Err(error) = handle_connection(stream, &games, &connections, id) {
+                    eprintln!("Error on connection {}: {}", id, error);
+                }
+                connections
+                    .lock()
+                    .expect("Poisoned mutex in Server run")
+                    .remove(&id);
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Stopper {
+    /// Stops accepting connections and closes every open one
+    pub fn stop(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        // Wake the listener so it sees the server is stopping
+        let _ = TcpStream::connect(self.address);
+        for stream in self
+            .connections
+            .lock()
+            .expect("Poisoned mutex in Stopper stop")
+            .values()
+        {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
+
+impl Game {
+    fn new() -> Self {
+        Game {
+            state: GameState::default(),
+            plays: vec![],
+            seats: HashMap::new(),
+        }
+    }
+
+    fn broadcast(&self, message: &ServerMessage) {
+        for seat in self.seats.values() {
+            if let Some((_, outbox)) = &seat.connection {
+                // A closed outbox means the connection is going away, which its own thread
+                // will notice and tidy up after
+                let _ = outbox.send(message.clone());
+            }
+        }
+    }
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    games: &Mutex<HashMap<String, Game>>,
+    connections: &Mutex<HashMap<u64, TcpStream>>,
+    id: u64,
+) -> io::Result<()> {
+    let reader = BufReader::new(stream.try_clone()?);
+    // Messages are written on their own thread so a slow client never holds up the server
+    // while it has the games locked
+    let (outbox, messages) = mpsc::channel::<ServerMessage>();
+    let mut writer = stream;
+    let writing = thread::spawn(move || {
+        for message in messages {
+            if send(&mut writer, &message).is_err() {
+                let _ = writer.shutdown(Shutdown::Both);
+                break;
+            }
+        }
+    });
+    let reject = |reason: String| {
+        let _ = outbox.send(ServerMessage::Rejected { reason });
+    };
+    let mut seated: Option<(String, Player)> = None;
+    for line in reader.lines() {
+        let message: ClientMessage = match serde_json::from_str(&line?) {
+            Ok(message) => message,
+            Err(error) => {
+                reject(format!("Did not understand message: {}", error));
+                continue;
+            }
+        };
+        match message {
+            ClientMessage::Join { game, seat, token } => {
+                if seated.is_some() {
+                    reject("Already seated in a game".to_string());
+                    continue;
+                }
+                let mut games = games.lock().expect("Poisoned mutex in Server join");
+                let entry = games.entry(game.clone()).or_insert_with(Game::new);
+                let connection = (id, outbox.clone());
+                let token = match entry.seats.get_mut(&seat) {
+                    Some(existing) => {
+                        if token.as_ref() != Some(&existing.token) {
+                            reject("Seat is taken".to_string());
+                            continue;
+                        }
+                        // The previous connection might not have noticed it dropped, so it is
+                        // closed to stop it playing for the seat as well
+                        if let Some((previous, _)) = existing.connection.replace(connection) {
+                            if let Some(stream) = connections
+                                .lock()
+                                .expect("Poisoned mutex in Server join")
+                                .get(&previous)
+                            {
+                                let _ = stream.shutdown(Shutdown::Both);
+                            }
+                        }
+                        existing.token.clone()
+                    }
+                    None => {
+                        let token = new_token();
+                        entry.seats.insert(
+                            seat,
+                            Seat {
+                                token: token.clone(),
+                                connection: Some(connection),
+                            },
+                        );
+                        token
+                    }
+                };
+                let _ = outbox.send(ServerMessage::Joined {
+                    game: game.clone(),
+                    seat,
+                    token,
+                });
+                let _ = outbox.send(ServerMessage::State {
+                    plays: entry.plays.clone(),
+                });
+                seated = Some((game, seat));
+            }
+            ClientMessage::Play { play } => {
+                let (game, seat) = match &seated {
+                    Some(seated) => seated,
+                    None => {
+                        reject("Must join a game before playing".to_string());
+                        continue;
+                    }
+                };
+                let mut games = games.lock().expect("Poisoned mutex in Server play");
+                let game = match games.get_mut(game) {
+                    Some(game) => game,
+                    None => {
+                        reject("The game has ended".to_string());
+                        continue;
+                    }
+                };
+                let current = game
+                    .seats
+                    .get(seat)
+                    .and_then(|seat| seat.connection.as_ref());
+                if !matches!(current, Some(&(connection, _)) if connection == id) {
+                    reject("The seat has been reclaimed by another connection".to_string());
+                    continue;
+                }
+                if game.state.turn() != *seat {
+                    reject("Not your turn".to_string());
+                    continue;
+                }
+                match game.state.make_play(&play) {
+                    Ok(game_state_update) => {
+                        game.plays.push(play.clone());
+                        game.broadcast(&ServerMessage::Played {
+                            play,
+                            game_state_update,
+                        });
+                    }
+                    Err(reason) => reject(format!("Play is illegal: {}", reason)),
+                }
+            }
+        }
+    }
+    // Leave the seat open to be reclaimed with its token, unless a reconnection has already
+    // taken it over, and drop the game once both seats have left
+    if let Some((game, seat)) = seated {
+        let mut games = games.lock().expect("Poisoned mutex in Server disconnect");
+        if let Some(entry) = games.get_mut(&game) {
+            if let Some(seat) = entry.seats.get_mut(&seat) {
+                if matches!(seat.connection, Some((connection, _)) if connection == id) {
+                    seat.connection = None;
+                }
+            }
+            if entry.seats.values().all(|seat| seat.connection.is_none()) {
+                games.remove(&game);
+            }
+        }
+    }
+    drop(outbox);
+    let _ = writing.join();
+    Ok(())
+}
+
+fn new_token() -> String {
+    use rand::prelude::*;
+    format!("{:016x}", rand::thread_rng().gen::<u64>())
+}
+
+#[test]
+fn games_are_dropped_once_both_seats_leave_and_the_server_stops() {
+    use crate::network::client::Client;
+    use std::time::Duration;
+
+    let server = Server::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    let games = server.games.clone();
+    let stopper = server.stopper().unwrap();
+    let running = thread::spawn(move || server.run());
+    let wait_until = |condition: &dyn Fn() -> bool| {
+        for _ in 0..500 {
+            if condition() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("Timed out waiting for the server");
+    };
+
+    let defender = Client::connect(address, "test", Player::Defender, None).unwrap();
+    let attacker = Client::connect(address, "test", Player::Attacker, None).unwrap();
+    drop(defender);
+    thread::sleep(Duration::from_millis(50));
+    assert!(games.lock().unwrap().contains_key("test"));
+    drop(attacker);
+    wait_until(&|| games.lock().unwrap().is_empty());
+
+    let connected = Client::connect(address, "other", Player::Defender, None).unwrap();
+    stopper.stop();
+    running.join().unwrap().unwrap();
+    wait_until(&|| !connected.state().connected);
+    assert!(Client::connect(address, "other", Player::Attacker, None).is_err());
+}
+
+#[test]
+fn reclaiming_a_seat_closes_the_previous_connection() {
+    use crate::network::client::Client;
+    use std::time::Duration;
+
+    let server = Server::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    thread::spawn(move || server.run());
+
+    let defender = Client::connect(address, "test", Player::Defender, None).unwrap();
+    let token = defender.token().to_string();
+    let reclaimed = Client::connect(address, "test", Player::Defender, Some(token)).unwrap();
+    for _ in 0..500 {
+        if !defender.state().connected {
+            break;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(!defender.state().connected);
+    assert!(defender
+        .make_play(Play {
+            from: (5, 3),
+            to: (2, 3),
+        })
+        .is_err());
+
+    let play = Play {
+        from: (5, 3),
+        to: (1, 3),
+    };
+    reclaimed.make_play(play.clone()).unwrap();
+    assert_eq!(Some(vec![play]), reclaimed.wait_for_plays(0));
+}
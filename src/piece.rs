@@ -1,14 +1,20 @@
+use serde::{Deserialize, Serialize};
+
 use std::cmp::PartialEq;
 use std::convert::TryFrom;
 use std::fmt::Display;
 
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, Eq, PartialEq, uniffi::Enum)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize, uniffi::Enum)]
 pub enum Tile {
     Empty = 0,
     Attacker = 1,
     Defender = 2,
     King = 3,
+    /// An attacker that can also capture by jumping, in Berserk Hnefatafl
+    Commander = 4,
+    /// A defender that can also capture by jumping, in Berserk Hnefatafl
+    Knight = 5,
 }
 
 impl Display for Tile {
@@ -21,16 +27,20 @@ impl Display for Tile {
                 Tile::Attacker => "A",
                 Tile::Defender => "D",
                 Tile::King => "K",
+                Tile::Commander => "C",
+                Tile::Knight => "N",
             }
         )
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum Piece {
     Attacker,
     Defender,
     King,
+    Commander,
+    Knight,
 }
 
 impl Display for Piece {
@@ -42,6 +52,8 @@ impl Display for Piece {
                 Piece::Attacker => "A",
                 Piece::Defender => "D",
                 Piece::King => "K",
+                Piece::Commander => "C",
+                Piece::Knight => "N",
             }
         )
     }
@@ -49,12 +61,14 @@ impl Display for Piece {
 
 impl PartialEq<Tile> for Piece {
     fn eq(&self, tile: &Tile) -> bool {
-        match (tile, self) {
+        matches!(
+            (tile, self),
             (Tile::Attacker, Piece::Attacker)
-            | (Tile::Defender, Piece::Defender)
-            | (Tile::King, Piece::King) => true,
-            _ => false,
-        }
+                | (Tile::Defender, Piece::Defender)
+                | (Tile::King, Piece::King)
+                | (Tile::Commander, Piece::Commander)
+                | (Tile::Knight, Piece::Knight)
+        )
     }
 }
 
@@ -70,6 +84,8 @@ impl From<Piece> for Tile {
             Piece::Attacker => Tile::Attacker,
             Piece::Defender => Tile::Defender,
             Piece::King => Tile::King,
+            Piece::Commander => Tile::Commander,
+            Piece::Knight => Tile::Knight,
         }
     }
 }
@@ -81,7 +97,34 @@ impl TryFrom<Tile> for Piece {
             Tile::Attacker => Ok(Piece::Attacker),
             Tile::Defender => Ok(Piece::Defender),
             Tile::King => Ok(Piece::King),
+            Tile::Commander => Ok(Piece::Commander),
+            Tile::Knight => Ok(Piece::Knight),
             Tile::Empty => Err(()),
         }
     }
 }
+
+/// A set of kinds of piece, such as the pieces allowed to stop on a square
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PieceSet(u8);
+
+impl PieceSet {
+    pub const NONE: PieceSet = PieceSet(0);
+    pub const ALL: PieceSet = PieceSet(u8::MAX);
+
+    pub fn of(pieces: &[Piece]) -> Self {
+        PieceSet(
+            pieces
+                .iter()
+                .fold(0, |set, &piece| set | PieceSet::bit(piece)),
+        )
+    }
+
+    pub fn contains(self, piece: Piece) -> bool {
+        self.0 & PieceSet::bit(piece) != 0
+    }
+
+    fn bit(piece: Piece) -> u8 {
+        1 << piece as u8
+    }
+}
@@ -0,0 +1,94 @@
+use crate::editor::{BoardEditor, EditError};
+use crate::piece::{Piece, Tile};
+use crate::rules::Rules;
+use crate::state::{GameState, Play, Player};
+
+use serde::{Deserialize, Serialize};
+
+/// A record of a game, which can be saved to disk and replayed later.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct GameRecord {
+    /// The position the game was played from, which is the default starting position played
+    /// with the default rules if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<Start>,
+    /// The main line of plays
+    pub plays: Vec<Play>,
+    pub winner: Option<Player>,
+    pub turn_count: u32,
+    /// Comments on the main line
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub comments: Vec<Comment>,
+    /// Alternatives to plays in the main line that were tried when studying the game
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variations: Vec<Variation>,
+}
+
+/// The position a game was played from, with the rules it was played with
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Start {
+    /// The tiles of the board in row major order
+    pub tiles: Vec<Tile>,
+    pub turn: Player,
+    /// The pieces captured before the game started
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dead: Vec<Piece>,
+    pub rules: Rules,
+}
+
+/// A comment on a line of plays
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Comment {
+    /// How many plays into the line the comment was made, with 0 for before the first play
+    pub after: usize,
+    pub text: String,
+}
+
+/// An alternative line of plays, tried in place of the play at an index of the line it
+/// branches from
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Variation {
+    /// The index of the play this line replaces
+    pub at: usize,
+    pub plays: Vec<Play>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub comments: Vec<Comment>,
+    /// Alternatives to plays in this line
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variations: Vec<Variation>,
+}
+
+impl GameRecord {
+    /// Creates a record of the plays made to reach the game state
+    pub fn new(plays: Vec<Play>, game_state: &GameState) -> Self {
+        GameRecord {
+            start: None,
+            plays,
+            winner: game_state.winner(),
+            turn_count: game_state.turn_count(),
+            comments: vec![],
+            variations: vec![],
+        }
+    }
+}
+
+impl Start {
+    /// Records the game's current position as the start of a game
+    pub fn new(game_state: &GameState) -> Self {
+        Start {
+            tiles: game_state.pieces().row_major_iter().collect(),
+            turn: game_state.turn(),
+            dead: game_state.dead().clone(),
+            rules: game_state.rules(),
+        }
+    }
+
+    /// Starts a game from the position, or returns every problem stopping it if it was saved
+    /// with a position that isn't valid
+    pub fn game_state(&self) -> Result<GameState, EditError> {
+        let mut editor = BoardEditor::with_tiles(self.rules, self.tiles.clone());
+        editor.set_turn(self.turn);
+        editor.set_dead(self.dead.clone());
+        editor.start()
+    }
+}
@@ -0,0 +1,173 @@
+use crate::piece::{Piece, PieceSet};
+use crate::state::Player;
+
+use serde::{Deserialize, Serialize};
+
+/// The optional rules a game is played with. Different tafl variants are made up of different
+/// combinations of these.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Rules {
+    /// A row of two or more pieces along the board edge can be captured in one play by
+    /// bracketing both ends while every piece in the row faces an enemy. The king can be part
+    /// of the row but is not captured with it.
+    pub shieldwall: bool,
+    /// The defenders win if the king is on the edge, able to move, and enclosed by defenders
+    /// that can never be captured.
+    pub edge_fort: bool,
+    /// The attackers win if they surround every defender with an unbroken ring, so none of
+    /// them can reach the edge.
+    pub encirclement: bool,
+    /// The empty throne counts towards capturing attackers and defenders alike.
+    pub hostile_throne: bool,
+    /// The king can be captured by three attackers when the throne is on his fourth side.
+    pub throne_adjacent_king_capture: bool,
+    /// How the king is captured
+    pub king_capture: KingCapture,
+    /// A piece that captures may move again, as long as every further move also captures. The
+    /// player can end the turn early instead.
+    pub berserk: bool,
+    /// The side that makes the first play of the game
+    pub first_player: Player,
+    /// The furthest the king can move in one play, if limited
+    pub king_range: Option<u8>,
+    /// The furthest any other piece can move in one play, if limited
+    pub piece_range: Option<u8>,
+    /// What happens when the player to move has no plays to make
+    pub stalemate: Stalemate,
+    /// What happens when the same position comes up for the third time
+    pub repetition: Repetition,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Stalemate {
+    /// The player who can't move loses
+    Loss,
+    /// The defenders lose if they can't move, but the game is drawn if the attackers can't
+    DefendersLoss,
+    /// The game is drawn
+    Draw,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Repetition {
+    /// Positions can repeat as often as the players like
+    Allowed,
+    /// The game is drawn
+    Draw,
+    /// The defenders lose, as they are the ones who can usually force a repetition
+    DefendersLoss,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KingCapture {
+    /// The king must be surrounded on all four sides, with the board edge counting as one
+    Surrounded,
+    /// The king must be surrounded on all four sides, so he can't be captured on the edge
+    EdgeSafe,
+    /// The king is captured like any other piece, between two attackers
+    Weak,
+}
+
+impl Rules {
+    /// Copenhagen Hnefatafl, as played by the World Tafl Federation
+    pub fn copenhagen() -> Self {
+        Rules {
+            shieldwall: true,
+            edge_fort: true,
+            encirclement: true,
+            hostile_throne: true,
+            throne_adjacent_king_capture: true,
+            king_capture: KingCapture::EdgeSafe,
+            berserk: false,
+            first_player: Player::Attacker,
+            king_range: None,
+            piece_range: None,
+            stalemate: Stalemate::Loss,
+            repetition: Repetition::DefendersLoss,
+        }
+    }
+
+    /// Berserk Hnefatafl, which is played with commanders and knights in its starting position
+    pub fn berserk() -> Self {
+        Rules {
+            shieldwall: false,
+            edge_fort: false,
+            encirclement: false,
+            hostile_throne: true,
+            throne_adjacent_king_capture: true,
+            king_capture: KingCapture::Surrounded,
+            berserk: true,
+            first_player: Player::Attacker,
+            king_range: None,
+            piece_range: None,
+            stalemate: Stalemate::Loss,
+            repetition: Repetition::Allowed,
+        }
+    }
+}
+
+impl Rules {
+    /// Returns the furthest the piece can move in one play, if limited
+    pub fn range(&self, piece: Piece) -> Option<u8> {
+        match piece {
+            Piece::King => self.king_range,
+            _ => self.piece_range,
+        }
+    }
+}
+
+impl Default for Rules {
+    /// The rules this game was originally written with
+    fn default() -> Self {
+        Rules {
+            shieldwall: false,
+            edge_fort: false,
+            encirclement: false,
+            hostile_throne: false,
+            throne_adjacent_king_capture: false,
+            king_capture: KingCapture::Surrounded,
+            berserk: false,
+            first_player: Player::Defender,
+            king_range: None,
+            piece_range: None,
+            stalemate: Stalemate::Loss,
+            repetition: Repetition::Allowed,
+        }
+    }
+}
+
+/// A square only some pieces can stop on or pass through
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RestrictedSquare {
+    pub position: (u8, u8),
+    /// The pieces allowed to end a play on the square
+    pub stop: PieceSet,
+    /// The pieces allowed to move through the square while it is empty
+    pub pass: PieceSet,
+}
+
+impl RestrictedSquare {
+    /// The corners and throne of a square board, which only the king can stop on. Other pieces
+    /// can pass through the empty throne, but the corners are never in the way of anything.
+    pub fn corners_and_throne(size: u8, throne: (u8, u8)) -> Vec<RestrictedSquare> {
+        let king = PieceSet::of(&[Piece::King]);
+        let last = size - 1;
+        let mut squares: Vec<RestrictedSquare> = [(0, 0), (last, 0), (0, last), (last, last)]
+            .iter()
+            .map(|&position| RestrictedSquare {
+                position,
+                stop: king,
+                pass: king,
+            })
+            .collect();
+        squares.push(RestrictedSquare {
+            position: throne,
+            stop: king,
+            pass: PieceSet::ALL,
+        });
+        squares
+    }
+}
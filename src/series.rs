@@ -0,0 +1,327 @@
+use crate::record::GameRecord;
+use crate::rules::Rules;
+use crate::state::{GameState, IllegalPlay, Play, Player};
+
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+/// A match of several games between two participants who swap sides after every game, as in
+/// two game tafl matches. It is scored by wins, then by how quickly the games were won.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Series {
+    /// The number of games in the series
+    pub games: u32,
+    pub rules: Rules,
+    /// The finished games, in the order they were played
+    pub records: Vec<GameRecord>,
+}
+
+/// One of the two participants in a series. The first plays the attackers in the first game.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, uniffi::Enum)]
+pub enum Participant {
+    First,
+    Second,
+}
+
+/// How a participant is doing in a series
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, uniffi::Record)]
+pub struct Standing {
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    /// The turns taken to win each game won, added up, which breaks ties in favour of whoever
+    /// won more quickly
+    pub win_turns: u32,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, uniffi::Enum)]
+pub enum SeriesError {
+    /// Every game in the series has already been played
+    SeriesOver,
+    /// The game isn't over so it can't be recorded
+    Unfinished,
+    /// The saved series couldn't be loaded
+    Deserialize(String),
+    /// A play in the game couldn't have been made, so the game can't be recorded
+    IllegalPlay { index: u32, reason: IllegalPlay },
+    /// The plays don't lead to the position the game ended in
+    Mismatch,
+}
+
+impl Series {
+    /// Creates a series of the number of games, played from the starting position for the
+    /// rules
+    pub fn new(games: u32, rules: Rules) -> Self {
+        Series {
+            games,
+            rules,
+            records: vec![],
+        }
+    }
+
+    /// Loads a series saved as JSON by `save`
+    pub fn load(json: &str) -> Result<Self, SeriesError> {
+        serde_json::from_str(json).map_err(|error| SeriesError::Deserialize(error.to_string()))
+    }
+
+    /// Saves the series as JSON, to be loaded later
+    pub fn save(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Series should always serialize")
+    }
+
+    /// Returns the starting position for each game in the series, which has commanders and
+    /// knights under the berserk rules
+    pub fn new_game(&self) -> GameState {
+        if self.rules.berserk {
+            GameState::berserk().with_rules(self.rules)
+        } else {
+            GameState::new(self.rules)
+        }
+    }
+
+    /// Returns the side the participant plays in the game at the index, swapping every game
+    pub fn side(participant: Participant, game: usize) -> Player {
+        match (participant, game % 2) {
+            (Participant::First, 0) | (Participant::Second, 1) => Player::Attacker,
+            _ => Player::Defender,
+        }
+    }
+
+    /// Returns the index of the game to be played next, if the series isn't over
+    pub fn current_game(&self) -> Option<usize> {
+        if self.is_over() {
+            None
+        } else {
+            Some(self.records.len())
+        }
+    }
+
+    /// Returns true once every game in the series has been played
+    pub fn is_over(&self) -> bool {
+        self.records.len() >= self.games as usize
+    }
+
+    /// Adds a finished game to the series, trusting its result
+    fn record(&mut self, record: GameRecord) -> Result<(), SeriesError> {
+        if self.is_over() {
+            return Err(SeriesError::SeriesOver);
+        }
+        self.records.push(record);
+        Ok(())
+    }
+
+    /// Adds the game to the series, if it is over. Games still being played should be ended
+    /// first, such as by `GameState::reach_turn_limit`.
+    ///
+    /// The plays are replayed from the starting position and the game is scored from the
+    /// replay, so a game is only taken at its word on endings that happen off the board, such
+    /// as a resignation or a draw, and only if the plays lead to the position it ended in.
+    pub fn record_game(
+        &mut self,
+        plays: Vec<Play>,
+        game_state: &GameState,
+    ) -> Result<(), SeriesError> {
+        if !game_state.is_over() {
+            return Err(SeriesError::Unfinished);
+        }
+        let mut replay = self.new_game();
+        for (index, play) in plays.iter().enumerate() {
+            replay
+                .make_play(play)
+                .map_err(|reason| SeriesError::IllegalPlay {
+                    index: index as u32,
+                    reason,
+                })?;
+        }
+        let winner = if replay.is_over() {
+            replay.winner()
+        } else if replay.pieces() == game_state.pieces()
+            && replay.turn_count() == game_state.turn_count()
+        {
+            game_state.winner()
+        } else {
+            return Err(SeriesError::Mismatch);
+        };
+        self.record(GameRecord {
+            plays,
+            winner,
+            turn_count: replay.turn_count(),
+            ..GameRecord::default()
+        })
+    }
+
+    /// Returns how the participant is doing in the games played so far
+    pub fn standing(&self, participant: Participant) -> Standing {
+        let mut standing = Standing::default();
+        for (game, record) in self.records.iter().enumerate() {
+            match record.winner {
+                Some(winner) if winner == Series::side(participant, game) => {
+                    standing.wins += 1;
+                    standing.win_turns += record.turn_count;
+                }
+                Some(_) => standing.losses += 1,
+                None => standing.draws += 1,
+            }
+        }
+        standing
+    }
+
+    /// Returns the participant ahead on wins, or on speed of winning if they have the same
+    /// score, or None if they are level on both.
+    pub fn leader(&self) -> Option<Participant> {
+        let first = self.standing(Participant::First);
+        let second = self.standing(Participant::Second);
+        match first
+            .points()
+            .cmp(&second.points())
+            .then(second.win_turns.cmp(&first.win_turns))
+        {
+            std::cmp::Ordering::Greater => Some(Participant::First),
+            std::cmp::Ordering::Less => Some(Participant::Second),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+}
+
+impl Standing {
+    /// Returns the score in half points, with two for a win and one for a draw
+    pub fn points(&self) -> u32 {
+        2 * self.wins + self.draws
+    }
+}
+
+impl Participant {
+    pub fn other(&self) -> Participant {
+        match self {
+            Participant::First => Participant::Second,
+            Participant::Second => Participant::First,
+        }
+    }
+}
+
+impl Display for Participant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "{}",
+            match self {
+                Participant::First => "First",
+                Participant::Second => "Second",
+            }
+        )
+    }
+}
+
+impl Display for SeriesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            SeriesError::SeriesOver => write!(f, "Every game in the series has been played"),
+            SeriesError::Unfinished => write!(f, "The game isn't over yet"),
+            SeriesError::Deserialize(error) => write!(f, "Unable to load series: {}", error),
+            SeriesError::IllegalPlay { index, reason } => {
+                write!(f, "Play {} in the game is illegal: {}", index + 1, reason)
+            }
+            SeriesError::Mismatch => {
+                write!(f, "The plays don't lead to the position the game ended in")
+            }
+        }
+    }
+}
+
+#[test]
+fn series_swaps_sides_and_breaks_ties_on_speed() {
+    let mut series = Series::new(2, Rules::default());
+    assert_eq!(Player::Attacker, Series::side(Participant::First, 0));
+    assert_eq!(Player::Defender, Series::side(Participant::First, 1));
+    // The first participant wins as the attackers, then loses as the defenders but the second
+    // participant took longer to win
+    series
+        .record(GameRecord {
+            winner: Some(Player::Attacker),
+            turn_count: 40,
+            ..GameRecord::default()
+        })
+        .unwrap();
+    assert_eq!(Some(Participant::First), series.leader());
+    series
+        .record(GameRecord {
+            winner: Some(Player::Attacker),
+            turn_count: 60,
+            ..GameRecord::default()
+        })
+        .unwrap();
+    assert!(series.is_over());
+    assert_eq!(None, series.current_game());
+    let first = series.standing(Participant::First);
+    let second = series.standing(Participant::Second);
+    assert_eq!((1, 1, 40), (first.wins, first.losses, first.win_turns));
+    assert_eq!((1, 1, 60), (second.wins, second.losses, second.win_turns));
+    assert_eq!(Some(Participant::First), series.leader());
+    assert_eq!(
+        Err(SeriesError::SeriesOver),
+        series.record(GameRecord::default())
+    );
+
+    let json = serde_json::to_string(&series).unwrap();
+    assert_eq!(series, serde_json::from_str(&json).unwrap());
+}
+
+#[test]
+fn series_only_records_finished_games() {
+    let mut series = Series::new(2, Rules::default());
+    let mut game_state = series.new_game();
+    let play = Play {
+        from: (3, 5),
+        to: (3, 4),
+    };
+    game_state.make_play(&play).unwrap();
+    assert_eq!(
+        Err(SeriesError::Unfinished),
+        series.record_game(vec![play.clone()], &game_state)
+    );
+    game_state.resign(Player::Attacker).unwrap();
+    assert_eq!(Ok(()), series.record_game(vec![play], &game_state));
+    assert_eq!(Some(1), series.current_game());
+    assert_eq!(1, series.standing(Participant::Second).wins);
+    // A game cut short is ended as a draw before it is recorded
+    let mut game_state = series.new_game();
+    game_state.reach_turn_limit().unwrap();
+    assert_eq!(Ok(()), series.record_game(vec![], &game_state));
+    assert_eq!(1, series.standing(Participant::First).draws);
+    assert_eq!(Ok(series.clone()), Series::load(&series.save()));
+    assert!(matches!(
+        Series::load("{"),
+        Err(SeriesError::Deserialize(_))
+    ));
+}
+
+#[test]
+fn series_replays_games_before_recording_them() {
+    let mut series = Series::new(2, Rules::berserk());
+    assert_eq!(GameState::berserk().pieces(), series.new_game().pieces());
+    let mut game_state = series.new_game();
+    game_state.resign(Player::Attacker).unwrap();
+    let illegal = Play {
+        from: (0, 0),
+        to: (0, 1),
+    };
+    assert_eq!(
+        Err(SeriesError::IllegalPlay {
+            index: 0,
+            reason: IllegalPlay::NoPiece,
+        }),
+        series.record_game(vec![illegal], &game_state)
+    );
+    let play = Play {
+        from: (3, 0),
+        to: (3, 2),
+    };
+    assert_eq!(
+        Err(SeriesError::Mismatch),
+        series.record_game(vec![play.clone()], &game_state)
+    );
+    assert!(series.records.is_empty());
+    assert_eq!(Ok(()), series.record_game(vec![], &game_state));
+    assert_eq!(Some(Player::Defender), series.records[0].winner);
+}
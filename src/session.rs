@@ -0,0 +1,76 @@
+use crate::bot::Bot;
+use crate::state::{GameState, GameStateUpdate, Play, Player};
+
+/// Who makes the plays for one side of a game
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Seat {
+    /// A person playing on this device
+    Human,
+    /// A bot, which plays automatically on its turns
+    Bot(Bot),
+    /// A player somewhere else, whose plays arrive over the network
+    Remote,
+}
+
+/// The seats for both sides of a game, which decide when bots should play
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Seats {
+    pub attackers: Seat,
+    pub defenders: Seat,
+}
+
+impl Seats {
+    /// Returns the seat that plays for the player
+    pub fn seat(&self, player: Player) -> Seat {
+        match player {
+            Player::Attacker => self.attackers,
+            Player::Defender => self.defenders,
+        }
+    }
+
+    /// Returns the bot that should make the next play, if the game is still going and it's
+    /// a bot's turn
+    pub fn bot_to_move(&self, game_state: &GameState) -> Option<Bot> {
+        if game_state.is_over() {
+            return None;
+        }
+        match self.seat(game_state.turn()) {
+            Seat::Bot(bot) => Some(bot),
+            Seat::Human | Seat::Remote => None,
+        }
+    }
+
+    /// Makes the next play if it's a bot's turn, returning the play and how it changed the
+    /// game, or None if it's a human or remote seat's turn or the game is over.
+    pub fn step(&self, game_state: &mut GameState) -> Option<(Play, GameStateUpdate)> {
+        let play = self.bot_to_move(game_state)?.play(game_state)?;
+        let update = game_state
+            .make_play(&play)
+            .expect("Using available plays should mean making a play never fails");
+        Some((play, update))
+    }
+}
+
+#[test]
+fn bots_step_only_on_their_own_turns() {
+    use crate::bot::Strategy;
+    let seats = Seats {
+        attackers: Seat::Bot(Bot::new(Strategy::Random)),
+        defenders: Seat::Human,
+    };
+    // Defenders move first by default, so the bot has to wait
+    let mut game_state = GameState::default();
+    assert_eq!(None, seats.step(&mut game_state));
+    game_state
+        .make_play(&Play {
+            from: (3, 5),
+            to: (3, 4),
+        })
+        .unwrap();
+    let (play, _) = seats
+        .step(&mut game_state)
+        .expect("The bot should play on its turn");
+    assert_eq!(Some(&play), game_state.last_play());
+    assert_eq!(Player::Defender, game_state.turn());
+    assert_eq!(None, seats.step(&mut game_state));
+}
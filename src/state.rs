@@ -1,13 +1,21 @@
 use easy_ml::matrices::views::MatrixMut;
 use easy_ml::matrices::Matrix;
 
+use crate::editor::SetupProblem;
 use crate::piece::{Piece, Tile};
+use crate::rules::{KingCapture, Repetition, RestrictedSquare, Rules, Stalemate};
 
+use serde::{Deserialize, Serialize};
+
+use std::collections::hash_map::DefaultHasher;
 use std::convert::TryInto;
 use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use std::ops::ControlFlow;
 use std::ops::{Index, IndexMut};
+use std::sync::Arc;
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
 pub enum Player {
     Defender,
     Attacker,
@@ -20,19 +28,62 @@ pub struct GameState {
     board: Board,
     turn: Player,
     winner: Option<Player>,
+    /// Whether the game ended without a winner
+    draw: bool,
+    /// How the game ended, if it is over
+    end_reason: Option<EndReason>,
+    /// The pieces captured by the last play, and where they were
+    captures: Vec<(Position, Piece)>,
+    last_play: Option<Play>,
+    /// The player who has offered the other a draw, if the offer is still open
+    draw_offer: Option<Player>,
+    /// The positions reached after each turn since the last capture, kept if the rules limit
+    /// repetition
+    positions: Option<Arc<History>>,
     dead: Vec<Piece>,
     turn_count: u32,
+    /// The player who made, or will make, the first play of the game, if a setup chose them
+    /// rather than leaving it to the rules
+    first_player: Option<Player>,
     // This is redundant state but extremely useful to have O(1) queries for
     king: Position,
+    rules: Rules,
+    /// The piece that just captured and may move again to capture more, when playing with
+    /// berserk captures
+    berserker: Option<Position>,
+}
+
+/// A position reached after a turn, linked to the ones before it so that copies of a game,
+/// such as those a bot makes while searching, share their history instead of each cloning it.
+#[derive(Debug)]
+struct History {
+    hash: u64,
+    /// How many pieces had been captured when the position was reached
+    dead: usize,
+    previous: Option<Arc<History>>,
+}
+
+impl Drop for History {
+    fn drop(&mut self) {
+        // unlink the history one position at a time, so a long one can't overflow the stack
+        let mut previous = self.previous.take();
+        while let Some(history) = previous {
+            previous = match Arc::try_unwrap(history) {
+                Ok(mut history) => history.previous.take(),
+                Err(_) => None,
+            };
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct Board {
     board: Matrix<Tile>,
     castle: Position,
+    restricted: Vec<RestrictedSquare>,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Play {
     pub from: Position,
     pub to: Position,
@@ -47,7 +98,7 @@ enum Direction {
 }
 
 #[repr(u8)]
-#[derive(Clone, Copy, Debug, Eq, PartialEq, uniffi::Enum)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, uniffi::Enum)]
 pub enum GameStateUpdate {
     DefenderWin = 0,
     AttackerWin = 1,
@@ -56,6 +107,114 @@ pub enum GameStateUpdate {
     /// Attackers captured a piece.
     AttackerCapture = 3,
     Nothing = 4,
+    /// Attackers ran out of time.
+    DefenderWinOnTime = 5,
+    /// Defenders ran out of time.
+    AttackerWinOnTime = 6,
+    /// Defenders captured a shieldwall of pieces along the edge.
+    DefenderShieldwallCapture = 7,
+    /// Attackers captured a shieldwall of pieces along the edge.
+    AttackerShieldwallCapture = 8,
+    /// The game ended without a winner.
+    Draw = 9,
+}
+
+/// How a game ended
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, uniffi::Enum)]
+pub enum EndReason {
+    /// The attackers captured the king.
+    KingCaptured,
+    /// The king reached a corner.
+    KingEscaped,
+    /// The king is in an edge fort the attackers can never break.
+    EdgeFort,
+    /// The attackers surrounded every defender.
+    Encircled,
+    /// The player to move had no plays to make.
+    NoPlays,
+    /// A player ran out of time.
+    Time,
+    /// A player resigned.
+    Resignation,
+    /// The players agreed to a draw.
+    Agreement,
+    /// The same position came up for the third time.
+    Repetition,
+    /// The game went on for longer than allowed, so was called a draw.
+    TurnLimit,
+}
+
+/// The winner of a finished game, or none if it was drawn, and how it ended
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct GameResult {
+    pub winner: Option<Player>,
+    pub reason: EndReason,
+}
+
+/// The reason a play can't be made
+#[derive(Clone, Copy, Debug, Eq, PartialEq, uniffi::Enum)]
+pub enum IllegalPlay {
+    /// The play starts or ends off the board.
+    OffBoard,
+    /// The game has already been won or drawn.
+    GameOver,
+    /// There is no piece to move at the starting position.
+    NoPiece,
+    /// The piece belongs to the other player.
+    OpponentsPiece,
+    /// The play starts and ends on the same square.
+    NotMoved,
+    /// Pieces can only move up, down, left or right.
+    NotStraight,
+    /// The piece can't move that far in one play.
+    OutOfRange,
+    /// Another piece is in the way.
+    ThroughPiece,
+    /// A square the piece can't pass through is in the way.
+    ThroughRestrictedSquare,
+    /// Another piece is on the destination.
+    OntoPiece,
+    /// The piece can't stop on the destination.
+    OntoRestrictedSquare,
+    /// Only the piece that just captured can move during a berserk chain.
+    NotBerserker,
+    /// Every play after the first in a berserk chain must capture.
+    NotCapturing,
+    /// There is no draw offer from the other player to accept or decline.
+    NoDrawOffer,
+    /// There is no berserk chain of captures to end.
+    NoBerserkChain,
+    /// The game has gone on for more turns than can be counted.
+    TooManyTurns,
+}
+
+impl Display for IllegalPlay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "{}",
+            match self {
+                IllegalPlay::OffBoard => "That square is off the board",
+                IllegalPlay::GameOver => "The game is over",
+                IllegalPlay::NoPiece => "There is no piece there to move",
+                IllegalPlay::OpponentsPiece => "That piece belongs to the other side",
+                IllegalPlay::NotMoved => "The piece has to move somewhere",
+                IllegalPlay::NotStraight => "Pieces can only move in straight lines",
+                IllegalPlay::OutOfRange => "That piece can't move that far",
+                IllegalPlay::ThroughPiece => "Another piece is in the way",
+                IllegalPlay::ThroughRestrictedSquare => {
+                    "That piece can't pass through a square in the way"
+                }
+                IllegalPlay::OntoPiece => "Another piece is already there",
+                IllegalPlay::OntoRestrictedSquare => "That piece can't stop there",
+                IllegalPlay::NotBerserker => "Only the piece that just captured can move",
+                IllegalPlay::NotCapturing => "The piece can only carry on by capturing",
+                IllegalPlay::NoDrawOffer => "There is no draw offer to answer",
+                IllegalPlay::NoBerserkChain => "There is no berserk chain to end",
+                IllegalPlay::TooManyTurns => "Ran out of turns to count",
+            }
+        )
+    }
 }
 
 impl Display for Player {
@@ -177,6 +336,12 @@ impl Board {
             || position == (w - 1, h - 1)
     }
 
+    fn restriction(&self, position: Position) -> Option<&RestrictedSquare> {
+        self.restricted
+            .iter()
+            .find(|square| square.position == position)
+    }
+
     fn on(&self, position: Position) -> bool {
         let (w, h) = self.size();
         let (x, y) = position;
@@ -212,7 +377,8 @@ impl Direction {
 }
 
 impl GameState {
-    fn new() -> Self {
+    /// Creates a game from the starting position, played with the rules.
+    pub fn new(rules: Rules) -> Self {
         #[rustfmt::skip]
         let board = {
             use crate::piece::Tile::Empty as E;
@@ -237,40 +403,173 @@ impl GameState {
             board: Board {
                 board,
                 castle: (5, 5),
+                restricted: RestrictedSquare::corners_and_throne(11, (5, 5)),
             },
-            turn: Player::Defender,
+            turn: rules.first_player,
             winner: None,
+            draw: false,
+            end_reason: None,
+            captures: vec![],
+            last_play: None,
+            draw_offer: None,
+            positions: None,
             dead: vec![],
             turn_count: 0,
+            first_player: None,
             king: (5, 5),
+            rules,
+            berserker: None,
         }
     }
 
-    pub fn from_setup(pieces: Matrix<Tile>, turn: Player, dead: Vec<Piece>) -> Self {
-        assert_eq!((11, 11), pieces.size(), "Board must be 11x11");
+    /// Creates a game of Berserk Hnefatafl, where the attackers' four front pieces are
+    /// commanders and the defenders' four outermost pieces are knights.
+    pub fn berserk() -> Self {
+        #[rustfmt::skip]
+        let board = {
+            use crate::piece::Tile::Empty as E;
+            use crate::piece::Tile::Attacker as A;
+            use crate::piece::Tile::Defender as D;
+            use crate::piece::Tile::King as K;
+            use crate::piece::Tile::Commander as C;
+            use crate::piece::Tile::Knight as N;
+            Matrix::from_flat_row_major((11, 11), vec![
+                E, E, E, A, A, A, A, A, E, E, E,
+                E, E, E, E, E, C, E, E, E, E, E,
+                E, E, E, E, E, E, E, E, E, E, E,
+                A, E, E, E, E, N, E, E, E, E, A,
+                A, E, E, E, D, D, D, E, E, E, A,
+                A, C, E, N, D, K, D, N, E, C, A,
+                A, E, E, E, D, D, D, E, E, E, A,
+                A, E, E, E, E, N, E, E, E, E, A,
+                E, E, E, E, E, E, E, E, E, E, E,
+                E, E, E, E, E, C, E, E, E, E, E,
+                E, E, E, A, A, A, A, A, E, E, E,
+            ])
+        };
+        let rules = Rules::berserk();
+        GameState::from_setup(board, rules.first_player, vec![])
+            .expect("The berserk starting position should always be valid")
+            .with_rules(rules)
+    }
+
+    /// Creates a game from the pieces on an 11x11 board, with the player to move first and the
+    /// pieces already captured. Returns the problem with the board instead if it is the wrong
+    /// size or doesn't have exactly one king.
+    pub fn from_setup(
+        pieces: Matrix<Tile>,
+        turn: Player,
+        dead: Vec<Piece>,
+    ) -> Result<Self, SetupProblem> {
+        if pieces.size() != (11, 11) {
+            return Err(SetupProblem::SizeMismatch {
+                expected: 11 * 11,
+                actual: (pieces.rows() * pieces.columns()) as u32,
+            });
+        }
         let board = pieces;
-        let (king, _) = board
+        // Indexes are (row, column) which is (y, x) for a position
+        let kings: Vec<(usize, usize)> = board
             .row_major_iter()
             .with_index()
-            .find(|&(_, tile)| tile == Tile::King)
-            .expect("1 king must be present in board");
-        GameState {
+            .filter(|&(_, tile)| tile == Tile::King)
+            .map(|(index, _)| index)
+            .collect();
+        let (y, x) = match kings[..] {
+            [king] => king,
+            [] => return Err(SetupProblem::NoKing),
+            _ => {
+                return Err(SetupProblem::TooManyKings {
+                    kings: kings.len() as u32,
+                })
+            }
+        };
+        Ok(GameState {
             board: Board {
                 board,
                 castle: (5, 5),
+                restricted: RestrictedSquare::corners_and_throne(11, (5, 5)),
             },
             turn,
             winner: None,
+            draw: false,
+            end_reason: None,
+            captures: vec![],
+            last_play: None,
+            draw_offer: None,
+            positions: None,
             dead,
             turn_count: 0,
-            king: (king.0 as u8, king.1 as u8),
+            first_player: Some(turn),
+            king: (x as u8, y as u8),
+            rules: Rules::default(),
+            berserker: None,
+        })
+    }
+
+    /// Changes the rules the game is played with. Until the first play is made, the side to
+    /// move follows the rules unless a setup chose it.
+    pub fn with_rules(mut self, rules: Rules) -> Self {
+        if self.turn_count == 0 && self.first_player.is_none() {
+            self.turn = rules.first_player;
+        }
+        self.rules = rules;
+        self
+    }
+
+    /// Replaces the squares only some pieces can stop on or pass through, which are the corners
+    /// and the throne by default.
+    pub fn with_restricted_squares(mut self, restricted: Vec<RestrictedSquare>) -> Self {
+        self.board.restricted = restricted;
+        self
+    }
+
+    /// Replaces the pieces and the side to move, as when the position arrives from elsewhere,
+    /// while keeping the rules, the turn count and the captured pieces. Pieces that are no
+    /// longer on the board are counted as captured, and a changed position counts as a turn.
+    pub fn set_position(&mut self, pieces: Matrix<Tile>, turn: Player) {
+        assert_eq!(
+            self.board.board.size(),
+            pieces.size(),
+            "Board size must not change"
+        );
+        let count =
+            |board: &Matrix<Tile>, tile| board.row_major_iter().filter(|&t| t == tile).count();
+        for tile in [
+            Tile::Attacker,
+            Tile::Defender,
+            Tile::King,
+            Tile::Commander,
+            Tile::Knight,
+        ] {
+            let captured = count(&self.board.board, tile).saturating_sub(count(&pieces, tile));
+            let piece: Result<Piece, _> = tile.try_into();
+            if let Ok(piece) = piece {
+                for _ in 0..captured {
+                    self.dead.push(piece);
+                }
+            }
+        }
+        if self.board.board != pieces {
+            self.turn_count += 1;
+        }
+        // Indexes are (row, column) which is (y, x) for a position
+        if let Some(((y, x), _)) = pieces
+            .row_major_iter()
+            .with_index()
+            .find(|&(_, tile)| tile == Tile::King)
+        {
+            self.king = (x as u8, y as u8);
         }
+        self.board.board = pieces;
+        self.turn = turn;
+        self.berserker = None;
     }
 }
 
 impl Default for GameState {
     fn default() -> Self {
-        GameState::new()
+        GameState::new(Rules::default())
     }
 }
 
@@ -299,13 +598,10 @@ impl Display for GameState {
 
 impl Player {
     fn owns(&self, piece: Piece) -> bool {
-        match self {
-            Player::Defender => piece != Piece::Attacker,
-            Player::Attacker => piece == Piece::Attacker,
-        }
+        piece.owner() == *self
     }
 
-    fn next(&self) -> Player {
+    pub fn next(&self) -> Player {
         match self {
             Player::Defender => Player::Attacker,
             Player::Attacker => Player::Defender,
@@ -316,8 +612,8 @@ impl Player {
 impl Piece {
     fn owner(&self) -> Player {
         match self {
-            Piece::Defender | Piece::King => Player::Defender,
-            Piece::Attacker => Player::Attacker,
+            Piece::Defender | Piece::King | Piece::Knight => Player::Defender,
+            Piece::Attacker | Piece::Commander => Player::Attacker,
         }
     }
 }
@@ -334,162 +630,388 @@ impl GameStateUpdate {
         match (self, event) {
             (GameStateUpdate::DefenderWin, _) => GameStateUpdate::DefenderWin,
             (GameStateUpdate::AttackerWin, _) => GameStateUpdate::AttackerWin,
+            (GameStateUpdate::DefenderWinOnTime, _) => GameStateUpdate::DefenderWinOnTime,
+            (GameStateUpdate::AttackerWinOnTime, _) => GameStateUpdate::AttackerWinOnTime,
             _ => event,
         }
     }
 }
 
 impl GameState {
-    pub fn make_play(&mut self, play: &Play) -> Result<GameStateUpdate, ()> {
+    pub fn make_play(&mut self, play: &Play) -> Result<GameStateUpdate, IllegalPlay> {
         if !self.board.on(play.from) || !self.board.on(play.to) {
-            return Err(());
+            return Err(IllegalPlay::OffBoard);
         }
-        if self.winner.is_some() {
-            return Err(());
+        if self.is_over() {
+            return Err(IllegalPlay::GameOver);
         }
-        let valid = match self.turn {
-            Player::Defender => self.is_valid_defender_play(play),
-            Player::Attacker => self.is_valid_attacker_play(play),
-        };
-        if !valid {
-            return Err(());
+        self.check_play(play)?;
+        if let Some(berserker) = self.berserker {
+            // only the piece that captured can carry on, and only by capturing again
+            if play.from != berserker {
+                return Err(IllegalPlay::NotBerserker);
+            }
+            if !self.captures(play) {
+                return Err(IllegalPlay::NotCapturing);
+            }
+        }
+        self.captures.clear();
+        self.last_play = Some(play.clone());
+        if self.draw_offer == Some(self.turn.next()) {
+            // playing on instead of answering a draw offer declines it
+            self.draw_offer = None;
+        }
+        let dead = self.dead.len();
+        let mut info = self.move_piece(play);
+        if info == GameStateUpdate::AttackerWin {
+            self.end_reason = Some(EndReason::KingCaptured);
+        }
+        if self.turn == Player::Attacker && self.rules.encirclement && self.is_encircled() {
+            info = self.win(info, GameStateUpdate::AttackerWin, EndReason::Encircled);
+        }
+        self.berserker = None;
+        if let Some(reason) = self.defender_victory() {
+            info = self.win(info, GameStateUpdate::DefenderWin, reason);
+        } else if self.rules.berserk
+            && self.dead.len() > dead
+            && info != GameStateUpdate::AttackerWin
+            && self.can_capture_from(play.to)
+        {
+            // the turn carries on until the piece stops capturing or the player ends it
+            self.berserker = Some(play.to);
+            return Ok(info);
+        } else {
+            info = self.pass_turn(info);
         }
+        self.finish_turn(info)
+    }
+
+    /// Ends a berserk chain of captures early, passing the turn to the other player.
+    pub fn end_turn(&mut self) -> Result<GameStateUpdate, IllegalPlay> {
+        if self.berserker.is_none() {
+            return Err(IllegalPlay::NoBerserkChain);
+        }
+        self.berserker = None;
+        self.captures.clear();
+        let info = self.pass_turn(GameStateUpdate::Nothing);
+        self.finish_turn(info)
+    }
+
+    /// Returns the piece that must make the next play of a berserk chain, if there is one
+    pub fn berserker(&self) -> Option<Position> {
+        self.berserker
+    }
+
+    /// Moves a piece for a play that is known to be valid, and carries out any captures.
+    fn move_piece(&mut self, play: &Play) -> GameStateUpdate {
+        let jumped = self.jumped(play);
         if self.king == play.from {
             self.king = play.to;
         }
         self.board.swap(play.from, play.to);
-        let mut info = self.check_capture(play);
-        if self.is_defender_victory() {
-            info = info.update(GameStateUpdate::DefenderWin);
-        } else {
-            self.turn = self.turn.next();
-            if info != GameStateUpdate::DefenderWin
-                && info != GameStateUpdate::AttackerWin
-                && self.available_plays().is_empty()
-            {
-                // give victory to the player that just stopped the other from being able to make
-                // any plays (this could be due to capturing all the Attacker's pieces or either
-                // player having pieces but being completely boxed in by the other)
-                match self.turn {
-                    Player::Defender => info = GameStateUpdate::AttackerWin,
-                    Player::Attacker => info = GameStateUpdate::DefenderWin,
-                }
-            }
+        let mut jump_info = GameStateUpdate::Nothing;
+        if let Some(position) = jumped {
+            jump_info = match self.board[position].owner() {
+                Some(Player::Attacker) => GameStateUpdate::DefenderCapture,
+                _ => GameStateUpdate::AttackerCapture,
+            };
+            self.capture(position);
+        }
+        match self.check_capture(play) {
+            GameStateUpdate::Nothing => jump_info,
+            info => info,
         }
+    }
+
+    /// Passes the turn to the other player, and ends the game as the rules say if they have no
+    /// plays to make.
+    fn pass_turn(&mut self, mut info: GameStateUpdate) -> GameStateUpdate {
+        self.turn = self.turn.next();
+        if info != GameStateUpdate::DefenderWin
+            && info != GameStateUpdate::AttackerWin
+            && !self.has_any_play()
+        {
+            // this could be due to capturing all the Attacker's pieces or either player having
+            // pieces but being completely boxed in by the other
+            let loss = match self.turn {
+                Player::Defender => GameStateUpdate::AttackerWin,
+                Player::Attacker => GameStateUpdate::DefenderWin,
+            };
+            info = match (self.rules.stalemate, self.turn) {
+                (Stalemate::Loss, _) => loss,
+                (Stalemate::DefendersLoss, Player::Defender) => loss,
+                (Stalemate::DefendersLoss, Player::Attacker) => GameStateUpdate::Draw,
+                (Stalemate::Draw, _) => GameStateUpdate::Draw,
+            };
+            self.end_reason = Some(EndReason::NoPlays);
+        }
+        info
+    }
+
+    /// Checks if the turn player has any play to make, stopping at the first one found rather
+    /// than listing all of them.
+    fn has_any_play(&self) -> bool {
+        let (w, h) = self.board.size();
+        (0..w)
+            .flat_map(|x| (0..h).map(move |y| (x, y)))
+            .filter(|&position| self.board[position].owner() == Some(self.turn))
+            .any(|position| {
+                self.visit_plays_from(position, |_| ControlFlow::Break(()))
+                    .is_break()
+            })
+    }
+
+    /// Records the winner, if there is one, and counts the turn that just finished.
+    fn finish_turn(&mut self, mut info: GameStateUpdate) -> Result<GameStateUpdate, IllegalPlay> {
         match info {
             GameStateUpdate::DefenderWin => self.winner = Some(Player::Defender),
             GameStateUpdate::AttackerWin => self.winner = Some(Player::Attacker),
+            GameStateUpdate::Draw => self.draw = true,
             _ => (),
         };
+        if !self.is_over() && self.rules.repetition != Repetition::Allowed {
+            let position = self.position_hash();
+            let dead = self.dead.len();
+            // positions from before a capture can never come up again, so there is no need to
+            // keep them
+            let previous = self.positions.take().filter(|history| history.dead == dead);
+            let repeats =
+                std::iter::successors(previous.as_deref(), |history| history.previous.as_deref())
+                    .filter(|history| history.hash == position)
+                    .count();
+            self.positions = Some(Arc::new(History {
+                hash: position,
+                dead,
+                previous,
+            }));
+            if repeats >= 2 {
+                self.end_reason = Some(EndReason::Repetition);
+                info = match self.rules.repetition {
+                    Repetition::DefendersLoss => {
+                        self.winner = Some(Player::Attacker);
+                        GameStateUpdate::AttackerWin
+                    }
+                    _ => {
+                        self.draw = true;
+                        GameStateUpdate::Draw
+                    }
+                };
+            }
+        }
         self.turn_count = match self.turn_count.checked_add(1) {
             Some(count) => count,
             None => {
                 eprintln!("Ran out of bits to count the turn with");
-                return Err(());
+                return Err(IllegalPlay::TooManyTurns);
             }
         };
         Ok(info)
     }
 
-    fn is_valid_defender_play(&self, play: &Play) -> bool {
-        // Does the piece exist at the starting position?
-        let from = self.board[play.from];
-        let piece: Result<Piece, _> = from.try_into();
-        let piece = match piece {
-            Ok(piece) => piece,
-            Err(_) => return false,
-        };
-        // Is this a piece we can even own?
-        if !Player::Defender.owns(piece) {
-            return false;
+    /// Ends the game with a loss for the player who ran out of time, if it isn't already over.
+    pub fn time_out(&mut self, player: Player) -> Result<GameStateUpdate, IllegalPlay> {
+        if self.is_over() {
+            return Err(IllegalPlay::GameOver);
         }
-        if !self.can_stop_at(piece, play.to) {
-            return false;
+        self.winner = Some(player.next());
+        self.end_reason = Some(EndReason::Time);
+        Ok(match player {
+            Player::Attacker => GameStateUpdate::DefenderWinOnTime,
+            Player::Defender => GameStateUpdate::AttackerWinOnTime,
+        })
+    }
+
+    /// Ends the game with a loss for the player who resigned, whether or not it is their turn.
+    pub fn resign(&mut self, player: Player) -> Result<GameStateUpdate, IllegalPlay> {
+        if self.is_over() {
+            return Err(IllegalPlay::GameOver);
+        }
+        self.winner = Some(player.next());
+        self.end_reason = Some(EndReason::Resignation);
+        self.draw_offer = None;
+        Ok(match player {
+            Player::Attacker => GameStateUpdate::DefenderWin,
+            Player::Defender => GameStateUpdate::AttackerWin,
+        })
+    }
+
+    /// Ends the game as a draw because it has gone on for longer than allowed, such as when a
+    /// series limits how many turns each game can take.
+    pub fn reach_turn_limit(&mut self) -> Result<GameStateUpdate, IllegalPlay> {
+        if self.is_over() {
+            return Err(IllegalPlay::GameOver);
+        }
+        self.draw = true;
+        self.end_reason = Some(EndReason::TurnLimit);
+        self.draw_offer = None;
+        Ok(GameStateUpdate::Draw)
+    }
+
+    /// Offers the other player a draw, which stays open until they answer it or play on.
+    pub fn offer_draw(&mut self, player: Player) -> Result<(), IllegalPlay> {
+        if self.is_over() {
+            return Err(IllegalPlay::GameOver);
+        }
+        self.draw_offer = Some(player);
+        Ok(())
+    }
+
+    /// Accepts the other player's open draw offer, ending the game.
+    pub fn accept_draw(&mut self, player: Player) -> Result<GameStateUpdate, IllegalPlay> {
+        self.answer_draw(player)?;
+        self.draw = true;
+        self.end_reason = Some(EndReason::Agreement);
+        Ok(GameStateUpdate::Draw)
+    }
+
+    /// Declines the other player's open draw offer.
+    pub fn decline_draw(&mut self, player: Player) -> Result<(), IllegalPlay> {
+        self.answer_draw(player)
+    }
+
+    /// Closes the other player's draw offer so the player can answer it.
+    fn answer_draw(&mut self, player: Player) -> Result<(), IllegalPlay> {
+        if self.is_over() {
+            return Err(IllegalPlay::GameOver);
+        }
+        if self.draw_offer != Some(player.next()) {
+            return Err(IllegalPlay::NoDrawOffer);
         }
-        self.is_valid_path(play.from, play.to)
+        self.draw_offer = None;
+        Ok(())
+    }
+
+    /// Hashes the pieces on the board and whose turn it is, to spot repeated positions.
+    fn position_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.turn.hash(&mut hasher);
+        for tile in self.board.board.row_major_iter() {
+            tile.hash(&mut hasher);
+        }
+        hasher.finish()
     }
 
-    fn is_valid_attacker_play(&self, play: &Play) -> bool {
+    /// Checks a play is legal for the turn player, apart from the rules of berserk chains.
+    fn check_play(&self, play: &Play) -> Result<(), IllegalPlay> {
         // Does the piece exist at the starting position?
-        let from = self.board[play.from];
-        let piece: Result<Piece, _> = from.try_into();
-        let piece = match piece {
-            Ok(piece) => piece,
-            Err(_) => return false,
-        };
+        let piece: Piece = self.board[play.from]
+            .try_into()
+            .map_err(|_| IllegalPlay::NoPiece)?;
         // Is this a piece we can even own?
-        if !Player::Attacker.owns(piece) {
-            return false;
+        if !self.turn.owns(piece) {
+            return Err(IllegalPlay::OpponentsPiece);
+        }
+        if self.jumped(play).is_none() {
+            self.check_path(piece, play.from, play.to)?;
+        }
+        if self.is_occupied(play.to) {
+            return Err(IllegalPlay::OntoPiece);
         }
         if !self.can_stop_at(piece, play.to) {
-            return false;
+            return Err(IllegalPlay::OntoRestrictedSquare);
         }
-        self.is_valid_path(play.from, play.to)
+        Ok(())
     }
 
     /// Is this piece allowed to be moved to this position assuming it has a path to it?
     fn can_stop_at(&self, piece: Piece, position: Position) -> bool {
-        let to = self.board[position];
-        match piece {
-            Piece::Defender | Piece::Attacker => {
-                to == Tile::Empty
-                    && position != self.board.castle
-                    && !self.board.is_corner(position)
-            }
-            Piece::King => to == Tile::Empty,
-        }
+        self.board[position] == Tile::Empty
+            && self
+                .board
+                .restriction(position)
+                .is_none_or(|square| square.stop.contains(piece))
     }
 
     /// Is this piece allowed to pass through this position during a movement?
     fn can_pass_through(&self, piece: Piece, position: Position) -> bool {
-        let to = self.board[position];
-        match piece {
-            Piece::Defender | Piece::Attacker => {
-                to == Tile::Empty && !self.board.is_corner(position)
-            }
-            Piece::King => to == Tile::Empty,
+        self.board[position] == Tile::Empty
+            && self
+                .board
+                .restriction(position)
+                .is_none_or(|square| square.pass.contains(piece))
+    }
+
+    /// Returns the enemy piece a commander or knight would capture by jumping over it to land
+    /// on the empty square directly behind, if the play is such a jump.
+    fn jumped(&self, play: &Play) -> Option<Position> {
+        if !self.rules.berserk || !matches!(self.board[play.from], Tile::Commander | Tile::Knight) {
+            return None;
+        }
+        let (x0, y0) = play.from;
+        let (x1, y1) = play.to;
+        if !((x0 == x1 && y0.abs_diff(y1) == 2) || (y0 == y1 && x0.abs_diff(x1) == 2)) {
+            return None;
         }
+        let over = self.board.step(play.from, direction(play.from, play.to))?;
+        let owner = self.board[over].owner()?;
+        let jumper = self.board[play.from].owner()?;
+        if owner == jumper || self.board[over] == Tile::King || self.is_occupied(play.to) {
+            return None;
+        }
+        Some(over)
     }
 
-    /// Checks if the path between from and to is unoccpied and a single horizontal or vertical
-    /// movement.
-    fn is_valid_path(&self, from: Position, to: Position) -> bool {
-        fn range(x0: u8, x1: u8) -> std::ops::RangeInclusive<u8> {
-            if x0 < x1 {
-                x0..=x1
+    /// Checks if a valid play would capture any pieces, looking at the board as it would be
+    /// after the play rather than making it on a copy of the game.
+    fn captures(&self, play: &Play) -> bool {
+        if self.jumped(play).is_some() {
+            return true;
+        }
+        let tile = |position| {
+            if position == play.from {
+                Tile::Empty
+            } else if position == play.to {
+                self.board[play.from]
             } else {
-                x1..=x0
+                self.board[position]
             }
-        }
+        };
+        !self.sandwiched(play.to, &tile).is_empty()
+            || (self.rules.shieldwall && !self.shieldwalled(play.to, &tile).is_empty())
+    }
+
+    /// Checks if the piece at the position has any play that would capture
+    fn can_capture_from(&self, position: Position) -> bool {
+        self.visit_plays_from(position, |play| match self.captures(&play) {
+            true => ControlFlow::Break(()),
+            false => ControlFlow::Continue(()),
+        })
+        .is_break()
+    }
+
+    /// Checks if the path between from and to is a single horizontal or vertical movement within
+    /// the piece's range, which it can pass through.
+    fn check_path(&self, piece: Piece, from: Position, to: Position) -> Result<(), IllegalPlay> {
         if from == to {
-            return false;
+            return Err(IllegalPlay::NotMoved);
         }
         let (x0, y0) = from;
         let (x1, y1) = to;
         if x0 != x1 && y0 != y1 {
             // movement must be up, down, left or right
-            return false;
+            return Err(IllegalPlay::NotStraight);
         }
-        if x0 == x1 {
-            for y in range(y0, y1) {
-                let position = (x0, y);
-                if y != y0 {
-                    if self.is_occupied(position) {
-                        return false;
-                    }
-                }
+        if let Some(limit) = self.rules.range(piece) {
+            if x0.abs_diff(x1) + y0.abs_diff(y1) > limit {
+                return Err(IllegalPlay::OutOfRange);
             }
-        } else {
-            for x in range(x0, x1) {
-                let position = (x, y0);
-                if x != x0 {
-                    if self.is_occupied(position) {
-                        return false;
-                    }
-                }
+        }
+        let direction = direction(from, to);
+        let mut position = from;
+        while let Some(next) = self.board.step(position, direction) {
+            if next == to {
+                break;
             }
+            if !self.can_pass_through(piece, next) {
+                return Err(if self.is_occupied(next) {
+                    IllegalPlay::ThroughPiece
+                } else {
+                    IllegalPlay::ThroughRestrictedSquare
+                });
+            }
+            position = next;
         }
-        true
+        Ok(())
     }
 
     fn is_occupied(&self, position: Position) -> bool {
@@ -498,151 +1020,491 @@ impl GameState {
 
     fn check_capture(&mut self, play: &Play) -> GameStateUpdate {
         let mut info = GameStateUpdate::Nothing;
-        for &next in self
-            .board
-            .adjacent(play.to)
-            .iter()
-            .filter_map(|p| p.as_ref())
-        {
-            match self.board[play.to] {
-                Tile::Attacker => match self.board[next] {
-                    Tile::Defender => {
+        for (position, capture) in self.sandwiched(play.to, &|position| self.board[position]) {
+            self.capture(position);
+            info = info.update(capture);
+        }
+        if self.rules.shieldwall {
+            for (position, capture) in self.shieldwalled(play.to, &|position| self.board[position])
+            {
+                self.capture(position);
+                info = info.update(capture);
+            }
+        }
+        info
+    }
+
+    /// Lists the pieces sandwiched by the piece at the position, reading the board through
+    /// `tile` so a play can be checked before it is made.
+    fn sandwiched(
+        &self,
+        to: Position,
+        tile: &impl Fn(Position) -> Tile,
+    ) -> Vec<(Position, GameStateUpdate)> {
+        let mut captures = Vec::new();
+        for &next in self.board.adjacent(to).iter().filter_map(|p| p.as_ref()) {
+            match tile(to) {
+                Tile::Attacker | Tile::Commander => match tile(next) {
+                    Tile::Defender | Tile::Knight => {
                         // check if other side of defender is an attacker
-                        let other_side = self.board.step(next, direction(play.to, next));
+                        let other_side = self.board.step(next, direction(to, next));
                         let capture = match other_side {
-                            Some(position) => {
-                                let owner = self.board[position].owner();
-                                match owner {
-                                    Some(Player::Attacker) => true,
-                                    // corner squares count towards a capture
-                                    _ => self.board.is_corner(position),
-                                }
-                            }
+                            Some(position) => self.is_hostile_to(position, Player::Defender, tile),
                             None => false,
                         };
                         if capture {
-                            if let Some(piece) = self.board[next].try_into().ok() {
-                                self.dead.push(piece);
-                            }
-                            self.board[next] = Tile::Empty;
-                            info = info.update(GameStateUpdate::AttackerCapture);
+                            captures.push((next, GameStateUpdate::AttackerCapture));
                         }
                     }
                     Tile::King => {
-                        // check if all sides of king are attackers
-                        let capture = self
-                            .board
-                            .adjacent(next)
-                            .iter()
-                            .map(|&side| match side {
-                                Some(position) => {
-                                    let owner = self.board[position].owner();
-                                    match owner {
-                                        Some(Player::Attacker) => true,
-                                        // corner squares count towards a capture
-                                        _ => self.board.is_corner(position),
+                        let capture = match self.rules.king_capture {
+                            KingCapture::Weak => {
+                                // check if other side of king is an attacker, as for a defender
+                                let other_side = self.board.step(next, direction(to, next));
+                                match other_side {
+                                    Some(position) => {
+                                        self.is_hostile_to(position, Player::Defender, tile)
                                     }
+                                    None => false,
                                 }
-                                // edge counts towards a capture for the king
-                                None => true,
-                            })
-                            .all(|c| c == true);
-                        if capture {
-                            if let Some(piece) = self.board[next].try_into().ok() {
-                                self.dead.push(piece);
                             }
-                            self.board[next] = Tile::Empty;
-                            info = info.update(GameStateUpdate::AttackerWin);
+                            KingCapture::Surrounded | KingCapture::EdgeSafe => {
+                                // check if all sides of king are attackers
+                                self.board.adjacent(next).iter().all(|&side| match side {
+                                    Some(position) => {
+                                        let owner = tile(position).owner();
+                                        match owner {
+                                            Some(Player::Attacker) => true,
+                                            // the throne can take the place of an attacker
+                                            // when the king is next to it
+                                            _ if position == self.board.castle => {
+                                                self.rules.throne_adjacent_king_capture
+                                            }
+                                            // corner squares count towards a capture
+                                            _ => self.board.is_corner(position),
+                                        }
+                                    }
+                                    // edge counts towards a capture for the king, unless the
+                                    // king is safe there
+                                    None => self.rules.king_capture == KingCapture::Surrounded,
+                                })
+                            }
+                        };
+                        if capture {
+                            captures.push((next, GameStateUpdate::AttackerWin));
                         }
                     }
                     _ => (),
                 },
-                Tile::Defender | Tile::King => {
-                    match self.board[next] {
-                        Tile::Attacker => {
-                            // check if other side of attacker is defender or king
-                            let other_side = self.board.step(next, direction(play.to, next));
-                            let capture = match other_side {
-                                Some(position) => {
-                                    let owner = self.board[position].owner();
-                                    match owner {
-                                        Some(Player::Defender) => true,
-                                        // corner squares count towards a capture
-                                        _ => self.board.is_corner(position),
-                                    }
-                                }
-                                None => false,
-                            };
-                            if capture {
-                                if let Some(piece) = self.board[next].try_into().ok() {
-                                    self.dead.push(piece);
-                                }
-                                self.board[next] = Tile::Empty;
-                                info = info.update(GameStateUpdate::DefenderCapture);
-                            }
+                Tile::Defender | Tile::King | Tile::Knight => {
+                    if tile(next).owner() == Some(Player::Attacker) {
+                        // check if other side of attacker is defender or king
+                        let other_side = self.board.step(next, direction(to, next));
+                        let capture = match other_side {
+                            Some(position) => self.is_hostile_to(position, Player::Attacker, tile),
+                            None => false,
+                        };
+                        if capture {
+                            captures.push((next, GameStateUpdate::DefenderCapture));
                         }
-                        _ => (),
                     }
                 }
                 Tile::Empty => unreachable!(),
             }
         }
+        captures
+    }
+
+    /// Checks if a position counts towards sandwiching a piece owned by the player, because it
+    /// holds an enemy piece or is a hostile square. An empty restricted square is hostile to
+    /// the player's pieces if they can't stop on it, though the throne only is when the rules
+    /// make it hostile. The board is read through `tile`, as for `sandwiched`.
+    fn is_hostile_to(
+        &self,
+        position: Position,
+        player: Player,
+        tile: &impl Fn(Position) -> Tile,
+    ) -> bool {
+        match tile(position).owner() {
+            Some(owner) => owner != player,
+            None => {
+                let piece = match player {
+                    Player::Attacker => Piece::Attacker,
+                    Player::Defender => Piece::Defender,
+                };
+                self.board.restriction(position).is_some_and(|square| {
+                    !square.stop.contains(piece)
+                        && (self.rules.hostile_throne || position != self.board.castle)
+                })
+            }
+        }
+    }
+
+    /// Lists the pieces of a shieldwall captured by the piece at the position bracketing one
+    /// end of it, reading the board through `tile` as for `sandwiched`.
+    fn shieldwalled(
+        &self,
+        to: Position,
+        tile: &impl Fn(Position) -> Tile,
+    ) -> Vec<(Position, GameStateUpdate)> {
+        let mut captures = Vec::new();
+        let owner = match tile(to).owner() {
+            Some(owner) => owner,
+            None => return captures,
+        };
+        let (w, h) = self.board.size();
+        let (x, y) = to;
+        // The directions along each edge we are on, paired with the direction into the board
+        let mut lines = Vec::with_capacity(4);
+        if x == 0 || x == w - 1 {
+            let inwards = if x == 0 {
+                Direction::Right
+            } else {
+                Direction::Left
+            };
+            lines.push((Direction::Up, inwards));
+            lines.push((Direction::Down, inwards));
+        }
+        if y == 0 || y == h - 1 {
+            let inwards = if y == 0 {
+                Direction::Down
+            } else {
+                Direction::Up
+            };
+            lines.push((Direction::Left, inwards));
+            lines.push((Direction::Right, inwards));
+        }
+        for (along, inwards) in lines {
+            let mut wall = Vec::new();
+            let mut next = self.board.step(to, along);
+            while let Some(position) = next {
+                match tile(position).owner() {
+                    Some(player) if player != owner => wall.push(position),
+                    _ => break,
+                }
+                next = self.board.step(position, along);
+            }
+            let bracketed = match next {
+                Some(position) => self.is_hostile_to(position, owner.next(), tile),
+                None => false,
+            };
+            let faced = wall.iter().all(|&position| {
+                self.board
+                    .step(position, inwards)
+                    .map(|front| tile(front).owner() == Some(owner))
+                    .unwrap_or(false)
+            });
+            if wall.len() < 2 || !bracketed || !faced {
+                continue;
+            }
+            for position in wall {
+                // The king can shelter in a shieldwall but is never captured by one
+                if tile(position) == Tile::King {
+                    continue;
+                }
+                captures.push((
+                    position,
+                    match owner {
+                        Player::Attacker => GameStateUpdate::AttackerShieldwallCapture,
+                        Player::Defender => GameStateUpdate::DefenderShieldwallCapture,
+                    },
+                ));
+            }
+        }
+        captures
+    }
+
+    /// Removes a captured piece from the board
+    fn capture(&mut self, position: Position) {
+        if let Ok(piece) = self.board[position].try_into() {
+            self.dead.push(piece);
+            self.captures.push((position, piece));
+        }
+        self.board[position] = Tile::Empty;
+    }
+
+    /// Returns how the defenders have won, if they have
+    fn defender_victory(&self) -> Option<EndReason> {
+        if self.board.is_corner(self.king) && self.board[self.king] == Tile::King {
+            Some(EndReason::KingEscaped)
+        } else if self.rules.edge_fort && self.is_edge_fort() {
+            Some(EndReason::EdgeFort)
+        } else {
+            None
+        }
+    }
+
+    /// Updates the info with a win, recording the reason if the game wasn't already over.
+    fn win(
+        &mut self,
+        info: GameStateUpdate,
+        win: GameStateUpdate,
+        reason: EndReason,
+    ) -> GameStateUpdate {
+        let info = info.update(win);
+        if info == win && self.end_reason.is_none() {
+            self.end_reason = Some(reason);
+        }
         info
     }
 
-    fn is_defender_victory(&self) -> bool {
+    /// Checks if the attackers have formed an unbroken ring around every defender, so none of
+    /// them can reach the edge.
+    fn is_encircled(&self) -> bool {
+        let (w, h) = self.board.size();
+        let index = |(x, y): Position| y as usize * w as usize + x as usize;
+        // Flood fill every square the defenders could reach if they had as many moves as
+        // they liked, as only attackers can stop them
+        let mut reachable = vec![false; w as usize * h as usize];
+        let mut unvisited = Vec::new();
+        for x in 0..w {
+            for y in 0..h {
+                if self.board[(x, y)].owner() == Some(Player::Defender) {
+                    reachable[index((x, y))] = true;
+                    unvisited.push((x, y));
+                }
+            }
+        }
+        while let Some(position) = unvisited.pop() {
+            if self.board.is_edge(position) {
+                return false;
+            }
+            for &next in self.board.adjacent(position).iter().flatten() {
+                if self.board[next].owner() != Some(Player::Attacker) && !reachable[index(next)] {
+                    reachable[index(next)] = true;
+                    unvisited.push(next);
+                }
+            }
+        }
+        true
+    }
+
+    /// Checks if the king is on the edge, able to move, and enclosed by defenders that the
+    /// attackers can never capture.
+    fn is_edge_fort(&self) -> bool {
+        if !self.board.is_edge(self.king) {
+            return false;
+        }
+        let king_can_move = self
+            .board
+            .adjacent(self.king)
+            .iter()
+            .flatten()
+            .any(|&position| !self.is_occupied(position));
+        if !king_can_move {
+            return false;
+        }
         let (w, h) = self.board.size();
-        self.board[(0, 0)] == Tile::King
-            || self.board[(w - 1, 0)] == Tile::King
-            || self.board[(0, h - 1)] == Tile::King
-            || self.board[(w - 1, h - 1)] == Tile::King
+        let index = |(x, y): Position| y as usize * w as usize + x as usize;
+        // Flood fill the squares the king can reach without going through a defender, which
+        // must not contain any attackers
+        let mut inside = vec![false; w as usize * h as usize];
+        let mut unvisited = vec![self.king];
+        inside[index(self.king)] = true;
+        while let Some(position) = unvisited.pop() {
+            for &next in self.board.adjacent(position).iter().flatten() {
+                match self.board[next] {
+                    Tile::Attacker | Tile::Commander => return false,
+                    Tile::Defender | Tile::Knight => (),
+                    Tile::Empty | Tile::King => {
+                        if !inside[index(next)] {
+                            inside[index(next)] = true;
+                            unvisited.push(next);
+                        }
+                    }
+                }
+            }
+        }
+        // Find the defenders that can never be captured as long as none of them move, by
+        // starting with all of them and removing any that could be sandwiched until none are
+        // left to remove
+        let mut safe = vec![false; w as usize * h as usize];
+        for x in 0..w {
+            for y in 0..h {
+                safe[index((x, y))] = matches!(self.board[(x, y)], Tile::Defender | Tile::Knight);
+            }
+        }
+        let protects = |side: Option<Position>, safe: &Vec<bool>| match side {
+            // nothing can stand on the other side of the edge
+            None => true,
+            Some(position) => {
+                safe[index(position)]
+                    || (inside[index(position)]
+                        && !self.is_hostile_to(position, Player::Defender, &|position| {
+                            self.board[position]
+                        }))
+            }
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for x in 0..w {
+                for y in 0..h {
+                    let position = (x, y);
+                    if !safe[index(position)] {
+                        continue;
+                    }
+                    let horizontal = protects(self.board.left(position), &safe)
+                        || protects(self.board.right(position), &safe);
+                    let vertical = protects(self.board.up(position), &safe)
+                        || protects(self.board.down(position), &safe);
+                    if !horizontal || !vertical {
+                        safe[index(position)] = false;
+                        changed = true;
+                    }
+                }
+            }
+        }
+        // Every defender enclosing the king's squares must be safe
+        (0..w)
+            .flat_map(|x| (0..h).map(move |y| (x, y)))
+            .filter(|&position| inside[index(position)])
+            .flat_map(|position| self.board.adjacent(position))
+            .flatten()
+            .filter(|&position| matches!(self.board[position], Tile::Defender | Tile::Knight))
+            .all(|position| safe[index(position)])
     }
 
     pub fn available_plays(&self) -> Vec<Play> {
-        if self.winner.is_some() {
+        if self.is_over() {
             return vec![];
         }
+        if let Some(berserker) = self.berserker {
+            return self.available_plays_from(berserker);
+        }
         let mut plays = Vec::new();
         let (w, h) = self.board.size();
         for x in 0..w {
             for y in 0..h {
-                let tile = self.board[(x, y)];
-                let piece: Piece = match tile.try_into() {
-                    Ok(piece) => piece,
-                    Err(_) => continue,
-                };
-                match (&self.turn, piece) {
-                    (Player::Defender, Piece::Defender | Piece::King)
-                    | (Player::Attacker, Piece::Attacker) => {
-                        for direction in Direction::directions() {
-                            let (mut x1, mut y1) = (x, y);
-                            while let Some(position) = self.board.step((x1, y1), direction) {
-                                if self.can_stop_at(piece, position) {
-                                    plays.push(Play {
-                                        from: (x, y),
-                                        to: position,
-                                    });
-                                }
-                                if self.can_pass_through(piece, position) {
-                                    x1 = position.0;
-                                    y1 = position.1;
-                                } else {
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                    _ => (),
+                let owner = self.board[(x, y)].owner();
+                if owner == Some(self.turn) {
+                    plays.extend(self.plays_from((x, y)));
                 }
             }
         }
         plays
     }
 
+    /// Returns the available plays for the piece at the position, which are none if there is no
+    /// piece there or it can't make the current play.
+    pub fn available_plays_from(&self, from: Position) -> Vec<Play> {
+        if self.is_over()
+            || !self.board.on(from)
+            || self.board[from].owner() != Some(self.turn)
+            || self.berserker.is_some_and(|berserker| berserker != from)
+        {
+            return vec![];
+        }
+        let plays = self.plays_from(from);
+        if self.berserker.is_some() {
+            return plays
+                .into_iter()
+                .filter(|play| self.captures(play))
+                .collect();
+        }
+        plays
+    }
+
+    /// Returns the plays the piece at the position could make, ignoring whose turn it is
+    pub fn plays_from(&self, from: Position) -> Vec<Play> {
+        let mut plays = Vec::new();
+        let _ = self.visit_plays_from(from, |play| {
+            plays.push(play);
+            ControlFlow::<()>::Continue(())
+        });
+        plays
+    }
+
+    /// Calls the visitor with each play the piece at the position could make, ignoring whose
+    /// turn it is, until the visitor breaks.
+    fn visit_plays_from<B>(
+        &self,
+        from: Position,
+        mut visit: impl FnMut(Play) -> ControlFlow<B>,
+    ) -> ControlFlow<B> {
+        let piece: Piece = match self.board[from].try_into() {
+            Ok(piece) => piece,
+            Err(_) => return ControlFlow::Continue(()),
+        };
+        let range = self.rules.range(piece).unwrap_or(u8::MAX);
+        for direction in Direction::directions() {
+            let mut next = from;
+            let mut distance = 0;
+            while let Some(position) = self.board.step(next, direction) {
+                distance += 1;
+                if distance > range {
+                    break;
+                }
+                if self.can_stop_at(piece, position) {
+                    visit(Play { from, to: position })?;
+                }
+                if self.can_pass_through(piece, position) {
+                    next = position;
+                } else {
+                    break;
+                }
+            }
+            if matches!(piece, Piece::Commander | Piece::Knight) {
+                let landing = self
+                    .board
+                    .step(from, direction)
+                    .and_then(|over| self.board.step(over, direction));
+                if let Some(to) = landing {
+                    let play = Play { from, to };
+                    if self.can_stop_at(piece, to) && self.jumped(&play).is_some() {
+                        visit(play)?;
+                    }
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
     pub fn winner(&self) -> Option<Player> {
         self.winner
     }
 
+    /// Returns how the game ended, if it is over
+    pub fn end_reason(&self) -> Option<EndReason> {
+        self.end_reason
+    }
+
+    /// Returns the pieces captured by the last play, and where they were
+    pub fn last_captures(&self) -> &[(Position, Piece)] {
+        &self.captures
+    }
+
+    /// Returns the player who has offered the other a draw, if the offer is still open
+    pub fn draw_offer(&self) -> Option<Player> {
+        self.draw_offer
+    }
+
+    /// Returns the winner and how the game ended, if it is over
+    pub fn result(&self) -> Option<GameResult> {
+        if !self.is_over() {
+            return None;
+        }
+        self.end_reason.map(|reason| GameResult {
+            winner: self.winner,
+            reason,
+        })
+    }
+
+    /// Returns the last play made, if any
+    pub fn last_play(&self) -> Option<&Play> {
+        self.last_play.as_ref()
+    }
+
+    /// Returns true if the game ended without a winner
+    pub fn is_draw(&self) -> bool {
+        self.draw
+    }
+
+    /// Returns true if the game has been won or drawn
+    pub fn is_over(&self) -> bool {
+        self.winner.is_some() || self.draw
+    }
+
     pub fn representation(&self) -> easy_ml::tensors::Tensor<f64, 3> {
         crate::bot::new_input(&self.board)
     }
@@ -655,6 +1517,11 @@ impl GameState {
         self.board.size()
     }
 
+    /// Returns a copy of the pieces on the board, in the same layout as taken by `from_setup`
+    pub fn pieces(&self) -> Matrix<Tile> {
+        self.board.board.clone()
+    }
+
     pub fn turn(&self) -> Player {
         self.turn
     }
@@ -663,11 +1530,1071 @@ impl GameState {
         &self.dead
     }
 
+    /// Returns the number of turns made so far. Even turn counts are the first player's turns
+    /// and odd turn counts are the other player's.
     pub fn turn_count(&self) -> u32 {
         self.turn_count
     }
 
+    pub fn first_player(&self) -> Player {
+        self.first_player.unwrap_or(self.rules.first_player)
+    }
+
+    pub fn rules(&self) -> Rules {
+        self.rules
+    }
+
     pub fn king_position(&self) -> Position {
         self.king
     }
+
+    /// Returns true if the position is one of the corners the king escapes to
+    pub fn is_corner(&self, position: Position) -> bool {
+        self.board.is_corner(position)
+    }
+
+    /// Returns a copy of the state as if it were the player's turn, with no berserk chain in
+    /// progress, for looking ahead at what either side could do next.
+    pub fn as_turn_of(&self, player: Player) -> GameState {
+        let mut copy = self.clone();
+        copy.turn = player;
+        copy.berserker = None;
+        copy
+    }
+}
+
+#[test]
+fn setup_stores_the_king_position_as_x_and_y() {
+    let mut pieces = Matrix::empty(Tile::Empty, (11, 11));
+    // Rows are y and columns are x
+    pieces.set(7, 3, Tile::King);
+    let game_state = GameState::from_setup(pieces, Player::Defender, vec![]).unwrap();
+    assert_eq!((3, 7), game_state.king_position());
+}
+
+#[test]
+fn setup_reports_problems_instead_of_panicking() {
+    let setup = |pieces| GameState::from_setup(pieces, Player::Defender, vec![]).map(|_| ());
+    assert_eq!(
+        Err(SetupProblem::SizeMismatch {
+            expected: 121,
+            actual: 100,
+        }),
+        setup(Matrix::empty(Tile::King, (10, 10)))
+    );
+    let mut pieces = Matrix::empty(Tile::Empty, (11, 11));
+    assert_eq!(Err(SetupProblem::NoKing), setup(pieces.clone()));
+    pieces.set(7, 3, Tile::King);
+    pieces.set(2, 3, Tile::King);
+    assert_eq!(Err(SetupProblem::TooManyKings { kings: 2 }), setup(pieces));
+}
+
+#[test]
+fn shieldwall_is_captured_along_the_edge() {
+    #[rustfmt::skip]
+    let board = {
+        use crate::piece::Tile::Empty as E;
+        use crate::piece::Tile::Attacker as A;
+        use crate::piece::Tile::Defender as D;
+        use crate::piece::Tile::King as K;
+        Matrix::from_flat_row_major((11, 11), vec![
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, K, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, A, A, A, E, E, E, E, E, E,
+            E, A, D, D, D, E, E, E, A, E, E,
+        ])
+    };
+    let play = Play {
+        from: (8, 10),
+        to: (5, 10),
+    };
+
+    let mut classic = GameState::from_setup(board.clone(), Player::Attacker, vec![]).unwrap();
+    assert_eq!(Ok(GameStateUpdate::Nothing), classic.make_play(&play));
+    assert!(classic.dead().is_empty());
+
+    let mut copenhagen = GameState::from_setup(board, Player::Attacker, vec![])
+        .unwrap()
+        .with_rules(Rules::copenhagen());
+    assert_eq!(
+        Ok(GameStateUpdate::AttackerShieldwallCapture),
+        copenhagen.make_play(&play)
+    );
+    assert_eq!(&vec![Piece::Defender; 3], copenhagen.dead());
+    for x in 2..=4 {
+        assert_eq!(Tile::Empty, copenhagen.board[(x, 10)]);
+    }
+}
+
+#[test]
+fn shieldwall_spares_the_king_and_needs_every_piece_faced() {
+    #[rustfmt::skip]
+    let board = {
+        use crate::piece::Tile::Empty as E;
+        use crate::piece::Tile::Attacker as A;
+        use crate::piece::Tile::Defender as D;
+        use crate::piece::Tile::King as K;
+        Matrix::from_flat_row_major((11, 11), vec![
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, D, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, A, A, A, A, E, A, E, E, E, E,
+            E, D, K, D, E, A, D, D, E, A, E,
+        ])
+    };
+    let mut game_state = GameState::from_setup(board, Player::Attacker, vec![])
+        .unwrap()
+        .with_rules(Rules::copenhagen());
+    // The corner brackets the left wall, but the king is spared
+    game_state
+        .make_play(&Play {
+            from: (4, 9),
+            to: (4, 10),
+        })
+        .unwrap();
+    assert_eq!(&vec![Piece::Defender; 2], game_state.dead());
+    assert_eq!(Tile::King, game_state.board[(2, 10)]);
+    game_state
+        .make_play(&Play {
+            from: (5, 5),
+            to: (5, 4),
+        })
+        .unwrap();
+    // The right wall isn't fully faced so it survives
+    assert_eq!(
+        Ok(GameStateUpdate::Nothing),
+        game_state.make_play(&Play {
+            from: (9, 10),
+            to: (8, 10),
+        })
+    );
+    assert_eq!(Tile::Defender, game_state.board[(6, 10)]);
+}
+
+#[test]
+fn edge_fort_wins_for_defenders() {
+    #[rustfmt::skip]
+    let board = {
+        use crate::piece::Tile::Empty as E;
+        use crate::piece::Tile::Attacker as A;
+        use crate::piece::Tile::Defender as D;
+        use crate::piece::Tile::King as K;
+        Matrix::from_flat_row_major((11, 11), vec![
+            E, E, E, A, A, A, A, A, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            A, E, E, E, E, E, E, E, E, E, A,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, D, D, E, E, E, E, E,
+            E, E, E, E, D, E, E, E, D, E, E,
+            E, E, E, E, D, K, D, E, E, E, E,
+        ])
+    };
+    let play = Play {
+        from: (8, 9),
+        to: (6, 9),
+    };
+
+    let mut classic = GameState::from_setup(board.clone(), Player::Defender, vec![]).unwrap();
+    assert_eq!(Ok(GameStateUpdate::Nothing), classic.make_play(&play));
+    assert_eq!(None, classic.winner());
+
+    let mut copenhagen = GameState::from_setup(board, Player::Defender, vec![])
+        .unwrap()
+        .with_rules(Rules::copenhagen());
+    assert_eq!(
+        Ok(GameStateUpdate::DefenderWin),
+        copenhagen.make_play(&play)
+    );
+    assert_eq!(Some(Player::Defender), copenhagen.winner());
+    assert_eq!(Some(EndReason::EdgeFort), copenhagen.end_reason());
+}
+
+#[test]
+fn breakable_edge_fort_does_not_win() {
+    #[rustfmt::skip]
+    let board = {
+        use crate::piece::Tile::Empty as E;
+        use crate::piece::Tile::Attacker as A;
+        use crate::piece::Tile::Defender as D;
+        use crate::piece::Tile::King as K;
+        Matrix::from_flat_row_major((11, 11), vec![
+            E, E, E, A, A, A, A, A, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            A, E, E, E, E, E, E, E, E, E, A,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, D, E, E, E, E, E,
+            E, E, E, E, D, E, E, E, D, E, E,
+            E, E, E, E, D, K, D, E, E, E, E,
+        ])
+    };
+    // The defender at the top of the fort could be captured from either side
+    let mut game_state = GameState::from_setup(board, Player::Defender, vec![])
+        .unwrap()
+        .with_rules(Rules::copenhagen());
+    assert_eq!(
+        Ok(GameStateUpdate::Nothing),
+        game_state.make_play(&Play {
+            from: (8, 9),
+            to: (6, 9),
+        })
+    );
+    assert_eq!(None, game_state.winner());
+}
+
+#[test]
+fn encirclement_wins_for_attackers() {
+    #[rustfmt::skip]
+    let board = {
+        use crate::piece::Tile::Empty as E;
+        use crate::piece::Tile::Attacker as A;
+        use crate::piece::Tile::Defender as D;
+        use crate::piece::Tile::King as K;
+        Matrix::from_flat_row_major((11, 11), vec![
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, A, A, A, E, E, E, E,
+            E, E, E, A, E, D, E, A, E, E, E,
+            E, E, E, A, E, K, E, A, E, E, E,
+            E, E, E, A, E, E, E, A, E, E, E,
+            E, E, E, E, A, E, A, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, A, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+        ])
+    };
+    let play = Play {
+        from: (5, 9),
+        to: (5, 7),
+    };
+
+    let mut classic = GameState::from_setup(board.clone(), Player::Attacker, vec![]).unwrap();
+    assert_eq!(Ok(GameStateUpdate::Nothing), classic.make_play(&play));
+    assert_eq!(None, classic.winner());
+
+    let mut copenhagen = GameState::from_setup(board.clone(), Player::Attacker, vec![])
+        .unwrap()
+        .with_rules(Rules::copenhagen());
+    assert_eq!(
+        Ok(GameStateUpdate::AttackerWin),
+        copenhagen.make_play(&play)
+    );
+    assert_eq!(Some(Player::Attacker), copenhagen.winner());
+
+    // A defender left outside the ring can still reach the edge
+    let mut outside = board;
+    *outside.try_get_reference_mut(1, 1).unwrap() = Tile::Defender;
+    let mut copenhagen = GameState::from_setup(outside, Player::Attacker, vec![])
+        .unwrap()
+        .with_rules(Rules::copenhagen());
+    assert_eq!(Ok(GameStateUpdate::Nothing), copenhagen.make_play(&play));
+    assert_eq!(None, copenhagen.winner());
+}
+
+#[test]
+fn empty_throne_is_hostile() {
+    #[rustfmt::skip]
+    let board = {
+        use crate::piece::Tile::Empty as E;
+        use crate::piece::Tile::Attacker as A;
+        use crate::piece::Tile::Defender as D;
+        use crate::piece::Tile::King as K;
+        Matrix::from_flat_row_major((11, 11), vec![
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            A, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, D, E, E, E, E, E,
+            E, E, E, E, E, E, A, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, D, E, E, E,
+            E, K, E, E, E, E, E, E, E, E, E,
+        ])
+    };
+    let hostile = Rules {
+        hostile_throne: true,
+        ..Rules::default()
+    };
+    let attacker_play = Play {
+        from: (0, 3),
+        to: (5, 3),
+    };
+    let defender_play = Play {
+        from: (7, 9),
+        to: (7, 5),
+    };
+
+    let mut classic = GameState::from_setup(board.clone(), Player::Attacker, vec![]).unwrap();
+    assert_eq!(
+        Ok(GameStateUpdate::Nothing),
+        classic.make_play(&attacker_play)
+    );
+    assert_eq!(
+        Ok(GameStateUpdate::Nothing),
+        classic.make_play(&defender_play)
+    );
+
+    let mut game_state = GameState::from_setup(board, Player::Attacker, vec![])
+        .unwrap()
+        .with_rules(hostile);
+    assert_eq!(
+        Ok(GameStateUpdate::AttackerCapture),
+        game_state.make_play(&attacker_play)
+    );
+    assert_eq!(
+        Ok(GameStateUpdate::DefenderCapture),
+        game_state.make_play(&defender_play)
+    );
+    assert_eq!(&vec![Piece::Defender, Piece::Attacker], game_state.dead());
+}
+
+#[test]
+fn king_next_to_throne_is_captured_on_three_sides() {
+    #[rustfmt::skip]
+    let board = {
+        use crate::piece::Tile::Empty as E;
+        use crate::piece::Tile::Attacker as A;
+        use crate::piece::Tile::Defender as D;
+        use crate::piece::Tile::King as K;
+        Matrix::from_flat_row_major((11, 11), vec![
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, A, E, E,
+            E, E, E, E, A, K, A, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, D, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+        ])
+    };
+    let rules = Rules {
+        throne_adjacent_king_capture: true,
+        ..Rules::default()
+    };
+    let capture = Play {
+        from: (8, 3),
+        to: (5, 3),
+    };
+
+    let mut classic = GameState::from_setup(board.clone(), Player::Attacker, vec![]).unwrap();
+    assert_eq!(Ok(GameStateUpdate::Nothing), classic.make_play(&capture));
+
+    let mut game_state = GameState::from_setup(board, Player::Attacker, vec![])
+        .unwrap()
+        .with_rules(rules);
+    assert_eq!(
+        Ok(GameStateUpdate::AttackerWin),
+        game_state.make_play(&capture)
+    );
+    assert_eq!(Some(Player::Attacker), game_state.winner());
+}
+
+#[cfg(test)]
+fn king_capture_result(
+    board: &Matrix<Tile>,
+    king_capture: KingCapture,
+    play: &Play,
+) -> GameStateUpdate {
+    let rules = Rules {
+        king_capture,
+        ..Rules::default()
+    };
+    let mut game_state = GameState::from_setup(board.clone(), Player::Attacker, vec![])
+        .unwrap()
+        .with_rules(rules);
+    game_state.make_play(play).unwrap()
+}
+
+#[test]
+fn king_capture_against_the_edge() {
+    #[rustfmt::skip]
+    let board = {
+        use crate::piece::Tile::Empty as E;
+        use crate::piece::Tile::Attacker as A;
+        use crate::piece::Tile::Defender as D;
+        use crate::piece::Tile::King as K;
+        Matrix::from_flat_row_major((11, 11), vec![
+            E, E, E, E, A, K, A, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, A, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, D, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+        ])
+    };
+    let play = Play {
+        from: (5, 3),
+        to: (5, 1),
+    };
+    assert_eq!(
+        GameStateUpdate::AttackerWin,
+        king_capture_result(&board, KingCapture::Surrounded, &play)
+    );
+    assert_eq!(
+        GameStateUpdate::Nothing,
+        king_capture_result(&board, KingCapture::EdgeSafe, &play)
+    );
+    assert_eq!(
+        GameStateUpdate::Nothing,
+        king_capture_result(&board, KingCapture::Weak, &play)
+    );
+}
+
+#[test]
+fn weak_king_is_captured_between_two_attackers() {
+    #[rustfmt::skip]
+    let board = {
+        use crate::piece::Tile::Empty as E;
+        use crate::piece::Tile::Attacker as A;
+        use crate::piece::Tile::Defender as D;
+        use crate::piece::Tile::King as K;
+        Matrix::from_flat_row_major((11, 11), vec![
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, A, E, E, E, E, E, E, E,
+            E, E, E, K, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, A, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, D, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+        ])
+    };
+    let play = Play {
+        from: (3, 6),
+        to: (3, 4),
+    };
+    assert_eq!(
+        GameStateUpdate::Nothing,
+        king_capture_result(&board, KingCapture::Surrounded, &play)
+    );
+    assert_eq!(
+        GameStateUpdate::Nothing,
+        king_capture_result(&board, KingCapture::EdgeSafe, &play)
+    );
+    assert_eq!(
+        GameStateUpdate::AttackerWin,
+        king_capture_result(&board, KingCapture::Weak, &play)
+    );
+}
+
+#[test]
+fn berserk_capture_lets_the_piece_capture_again() {
+    #[rustfmt::skip]
+    let board = {
+        use crate::piece::Tile::Empty as E;
+        use crate::piece::Tile::Attacker as A;
+        use crate::piece::Tile::Defender as D;
+        use crate::piece::Tile::King as K;
+        Matrix::from_flat_row_major((11, 11), vec![
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            A, E, E, D, A, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, D, E, E, E, E, E, E, E, E,
+            E, E, A, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, K, E, E,
+            E, E, E, E, E, E, E, E, E, D, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+        ])
+    };
+    let rules = Rules {
+        berserk: true,
+        ..Rules::default()
+    };
+    let capture = Play {
+        from: (0, 2),
+        to: (2, 2),
+    };
+    let again = Play {
+        from: (2, 2),
+        to: (2, 4),
+    };
+
+    let mut classic = GameState::from_setup(board.clone(), Player::Attacker, vec![]).unwrap();
+    classic.make_play(&capture).unwrap();
+    assert_eq!(Player::Defender, classic.turn());
+    assert_eq!(None, classic.berserker());
+
+    let mut game_state = GameState::from_setup(board, Player::Attacker, vec![])
+        .unwrap()
+        .with_rules(rules);
+    assert_eq!(
+        Ok(GameStateUpdate::AttackerCapture),
+        game_state.make_play(&capture)
+    );
+    assert_eq!(Player::Attacker, game_state.turn());
+    assert_eq!(Some((2, 2)), game_state.berserker());
+    assert_eq!(0, game_state.turn_count());
+    assert_eq!(vec![again.clone()], game_state.available_plays());
+    assert_eq!(vec![again.clone()], game_state.available_plays_from((2, 2)));
+    assert!(game_state.available_plays_from((4, 2)).is_empty());
+    // Only capturing plays by the same piece can carry on the turn
+    assert_eq!(
+        Err(IllegalPlay::NotCapturing),
+        game_state.make_play(&Play {
+            from: (2, 2),
+            to: (1, 2),
+        })
+    );
+    assert_eq!(
+        Err(IllegalPlay::NotBerserker),
+        game_state.make_play(&Play {
+            from: (4, 2),
+            to: (4, 3),
+        })
+    );
+
+    let mut ended = game_state.clone();
+    assert_eq!(Ok(GameStateUpdate::Nothing), ended.end_turn());
+    assert_eq!(Player::Defender, ended.turn());
+    assert_eq!(1, ended.turn_count());
+    assert_eq!(Err(IllegalPlay::NoBerserkChain), ended.end_turn());
+
+    assert_eq!(
+        Ok(GameStateUpdate::AttackerCapture),
+        game_state.make_play(&again)
+    );
+    assert_eq!(Player::Defender, game_state.turn());
+    assert_eq!(None, game_state.berserker());
+    assert_eq!(1, game_state.turn_count());
+    assert_eq!(&vec![Piece::Defender, Piece::Defender], game_state.dead());
+}
+
+#[test]
+fn knights_and_commanders_capture_by_jumping() {
+    #[rustfmt::skip]
+    let board = {
+        use crate::piece::Tile::Empty as E;
+        use crate::piece::Tile::Attacker as A;
+        use crate::piece::Tile::King as K;
+        use crate::piece::Tile::Commander as C;
+        use crate::piece::Tile::Knight as N;
+        Matrix::from_flat_row_major((11, 11), vec![
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, N, A, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, C, K, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+        ])
+    };
+    let jump = Play {
+        from: (3, 3),
+        to: (5, 3),
+    };
+    // Only the berserk rules let pieces jump
+    let classic = GameState::from_setup(board.clone(), Player::Defender, vec![]).unwrap();
+    assert!(!classic.available_plays().contains(&jump));
+    assert!(classic.clone().make_play(&jump).is_err());
+    let mut game_state = GameState::from_setup(board, Player::Defender, vec![])
+        .unwrap()
+        .with_rules(Rules::berserk());
+    assert!(game_state.available_plays().contains(&jump));
+    assert_eq!(
+        Ok(GameStateUpdate::DefenderCapture),
+        game_state.make_play(&jump)
+    );
+    assert_eq!(&vec![Piece::Attacker], game_state.dead());
+    assert_eq!(Tile::Empty, game_state.pieces().get(3, 4));
+    // The king can't be captured by jumping over him
+    assert!(!game_state.available_plays().contains(&Play {
+        from: (7, 8),
+        to: (9, 8),
+    }));
+}
+
+#[test]
+fn starting_side_follows_the_rules() {
+    assert_eq!(Player::Defender, GameState::default().turn());
+    let changed = GameState::default().with_rules(Rules::copenhagen());
+    assert_eq!(Player::Attacker, changed.turn());
+    assert_eq!(Player::Attacker, changed.first_player());
+    // A setup keeps the side it chose to move first
+    let setup = GameState::from_setup(GameState::default().pieces(), Player::Defender, vec![])
+        .unwrap()
+        .with_rules(Rules::copenhagen());
+    assert_eq!(Player::Defender, setup.turn());
+    assert_eq!(Player::Defender, setup.first_player());
+
+    let mut game_state = GameState::new(Rules::copenhagen());
+    assert_eq!(Player::Attacker, game_state.turn());
+    assert_eq!(Player::Attacker, game_state.first_player());
+    game_state
+        .make_play(&Play {
+            from: (3, 0),
+            to: (3, 2),
+        })
+        .unwrap();
+    assert_eq!(Player::Defender, game_state.turn());
+    assert_eq!(1, game_state.turn_count());
+    assert_eq!(Player::Attacker, game_state.first_player());
+}
+
+#[test]
+fn restricted_squares_and_ranges_limit_movement() {
+    use crate::piece::PieceSet;
+
+    #[rustfmt::skip]
+    let board = {
+        use crate::piece::Tile::Empty as E;
+        use crate::piece::Tile::Attacker as A;
+        use crate::piece::Tile::Defender as D;
+        use crate::piece::Tile::King as K;
+        Matrix::from_flat_row_major((11, 11), vec![
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, D, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, K, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, A, E,
+        ])
+    };
+    let through_throne = Play {
+        from: (1, 5),
+        to: (8, 5),
+    };
+    let king_far = Play {
+        from: (1, 9),
+        to: (8, 9),
+    };
+    let classic = GameState::from_setup(board.clone(), Player::Defender, vec![]).unwrap();
+    assert!(classic.available_plays().contains(&through_throne));
+    assert!(classic.available_plays().contains(&king_far));
+
+    let mut restricted = RestrictedSquare::corners_and_throne(11, (5, 5));
+    restricted.last_mut().unwrap().pass = PieceSet::of(&[Piece::King]);
+    restricted.push(RestrictedSquare {
+        position: (1, 2),
+        stop: PieceSet::NONE,
+        pass: PieceSet::ALL,
+    });
+    let rules = Rules {
+        king_range: Some(3),
+        ..Rules::default()
+    };
+    let mut game_state = GameState::from_setup(board, Player::Defender, vec![])
+        .unwrap()
+        .with_rules(rules)
+        .with_restricted_squares(restricted);
+    let plays = game_state.available_plays();
+    assert!(!plays.contains(&through_throne));
+    assert!(plays.contains(&Play {
+        from: (1, 5),
+        to: (4, 5),
+    }));
+    assert!(!plays.contains(&king_far));
+    assert!(plays.contains(&Play {
+        from: (1, 9),
+        to: (4, 9),
+    }));
+    assert!(!plays.contains(&Play {
+        from: (1, 5),
+        to: (1, 2),
+    }));
+    assert!(plays.contains(&Play {
+        from: (1, 5),
+        to: (1, 1),
+    }));
+    assert_eq!(
+        Err(IllegalPlay::ThroughRestrictedSquare),
+        game_state.make_play(&through_throne)
+    );
+    assert_eq!(
+        Err(IllegalPlay::OutOfRange),
+        game_state.make_play(&king_far)
+    );
+    assert_eq!(
+        Err(IllegalPlay::OntoRestrictedSquare),
+        game_state.make_play(&Play {
+            from: (1, 5),
+            to: (1, 2),
+        })
+    );
+}
+
+#[test]
+fn restricted_squares_are_hostile_to_pieces_that_cant_stop_on_them() {
+    use crate::piece::PieceSet;
+
+    #[rustfmt::skip]
+    let board = {
+        use crate::piece::Tile::Empty as E;
+        use crate::piece::Tile::Attacker as A;
+        use crate::piece::Tile::Defender as D;
+        use crate::piece::Tile::King as K;
+        Matrix::from_flat_row_major((11, 11), vec![
+            E, D, E, E, E, E, A, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, D, E, E, E, E, E,
+            E, E, A, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, K, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+        ])
+    };
+    let beside_wall = Play {
+        from: (6, 0),
+        to: (6, 3),
+    };
+    let beside_corner = Play {
+        from: (2, 4),
+        to: (2, 0),
+    };
+    // Only the throne is restricted, with a wall next to the defender that nothing can stop on
+    let restricted = vec![
+        RestrictedSquare {
+            position: (5, 5),
+            stop: PieceSet::of(&[Piece::King]),
+            pass: PieceSet::ALL,
+        },
+        RestrictedSquare {
+            position: (4, 3),
+            stop: PieceSet::NONE,
+            pass: PieceSet::NONE,
+        },
+    ];
+    let game_state = GameState::from_setup(board, Player::Attacker, vec![])
+        .unwrap()
+        .with_restricted_squares(restricted);
+
+    let mut wall = game_state.clone();
+    assert_eq!(
+        Ok(GameStateUpdate::AttackerCapture),
+        wall.make_play(&beside_wall)
+    );
+    assert_eq!(Tile::Empty, wall.pieces().get(3, 5));
+    // The corner is an ordinary square once it isn't restricted
+    let mut corner = game_state;
+    assert_eq!(
+        Ok(GameStateUpdate::Nothing),
+        corner.make_play(&beside_corner)
+    );
+    assert_eq!(Tile::Defender, corner.pieces().get(0, 1));
+}
+
+#[test]
+fn stalemate_outcome_follows_the_rules() {
+    #[rustfmt::skip]
+    let board = {
+        use crate::piece::Tile::Empty as E;
+        use crate::piece::Tile::Attacker as A;
+        use crate::piece::Tile::Defender as D;
+        use crate::piece::Tile::King as K;
+        Matrix::from_flat_row_major((11, 11), vec![
+            E, A, D, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, D, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, K, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+        ])
+    };
+    // Leaves the attacker boxed in between the corner and two defenders
+    let play = Play {
+        from: (1, 3),
+        to: (1, 1),
+    };
+    let outcome = |stalemate| {
+        let rules = Rules {
+            stalemate,
+            ..Rules::default()
+        };
+        let mut game_state = GameState::from_setup(board.clone(), Player::Defender, vec![])
+            .unwrap()
+            .with_rules(rules);
+        let update = game_state.make_play(&play).unwrap();
+        (update, game_state.winner(), game_state.is_over())
+    };
+    assert_eq!(
+        (GameStateUpdate::DefenderWin, Some(Player::Defender), true),
+        outcome(Stalemate::Loss)
+    );
+    assert_eq!(
+        (GameStateUpdate::Draw, None, true),
+        outcome(Stalemate::DefendersLoss)
+    );
+    assert_eq!(
+        (GameStateUpdate::Draw, None, true),
+        outcome(Stalemate::Draw)
+    );
+}
+
+#[test]
+fn illegal_plays_say_why() {
+    let mut game_state = GameState::default();
+    let mut check = |from, to| game_state.make_play(&Play { from, to });
+    assert_eq!(Err(IllegalPlay::OffBoard), check((0, 0), (0, 11)));
+    assert_eq!(Err(IllegalPlay::NoPiece), check((0, 0), (0, 1)));
+    assert_eq!(Err(IllegalPlay::OpponentsPiece), check((3, 0), (3, 2)));
+    assert_eq!(Err(IllegalPlay::NotMoved), check((5, 3), (5, 3)));
+    assert_eq!(Err(IllegalPlay::NotStraight), check((5, 3), (7, 1)));
+    assert_eq!(Err(IllegalPlay::ThroughPiece), check((5, 5), (5, 2)));
+    assert_eq!(Err(IllegalPlay::OntoPiece), check((5, 3), (5, 1)));
+}
+
+#[test]
+fn available_plays_from_a_position_are_the_turn_players() {
+    let game_state = GameState::default();
+    let from = (5, 3);
+    let expected: Vec<Play> = game_state
+        .available_plays()
+        .into_iter()
+        .filter(|play| play.from == from)
+        .collect();
+    assert_eq!(expected, game_state.available_plays_from(from));
+    assert_eq!(9, expected.len());
+    // Not the turn player's piece, an empty tile and off the board
+    assert!(game_state.available_plays_from((5, 1)).is_empty());
+    assert!(game_state.available_plays_from((0, 0)).is_empty());
+    assert!(game_state.available_plays_from((11, 0)).is_empty());
+}
+
+#[test]
+fn captures_are_listed_with_their_positions() {
+    #[rustfmt::skip]
+    let board = {
+        use crate::piece::Tile::Empty as E;
+        use crate::piece::Tile::Attacker as A;
+        use crate::piece::Tile::Defender as D;
+        use crate::piece::Tile::King as K;
+        Matrix::from_flat_row_major((11, 11), vec![
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, A, D, E, D, A, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, A, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, K, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+        ])
+    };
+    let mut game_state = GameState::from_setup(board, Player::Attacker, vec![]).unwrap();
+    assert_eq!(
+        Ok(GameStateUpdate::AttackerCapture),
+        game_state.make_play(&Play {
+            from: (5, 6),
+            to: (5, 3),
+        })
+    );
+    assert_eq!(
+        &[((4, 3), Piece::Defender), ((6, 3), Piece::Defender)],
+        game_state.last_captures()
+    );
+    assert_eq!(None, game_state.end_reason());
+    game_state
+        .make_play(&Play {
+            from: (8, 8),
+            to: (8, 9),
+        })
+        .unwrap();
+    assert!(game_state.last_captures().is_empty());
+}
+
+#[test]
+fn resigning_and_agreeing_a_draw_end_the_game() {
+    let mut resigned = GameState::default();
+    assert_eq!(
+        Ok(GameStateUpdate::AttackerWin),
+        resigned.resign(Player::Defender)
+    );
+    assert_eq!(
+        Some(GameResult {
+            winner: Some(Player::Attacker),
+            reason: EndReason::Resignation,
+        }),
+        resigned.result()
+    );
+    assert_eq!(
+        Err(IllegalPlay::GameOver),
+        resigned.resign(Player::Attacker)
+    );
+
+    let mut game_state = GameState::default();
+    assert_eq!(None, game_state.result());
+    assert_eq!(
+        Err(IllegalPlay::NoDrawOffer),
+        game_state.accept_draw(Player::Attacker)
+    );
+    game_state.offer_draw(Player::Defender).unwrap();
+    // Players can't answer their own offer
+    assert_eq!(
+        Err(IllegalPlay::NoDrawOffer),
+        game_state.accept_draw(Player::Defender)
+    );
+    assert_eq!(Ok(()), game_state.decline_draw(Player::Attacker));
+    assert_eq!(None, game_state.draw_offer());
+
+    // The offer stays open through the offering player's own play, but lapses if the other
+    // player plays on
+    game_state.offer_draw(Player::Defender).unwrap();
+    game_state
+        .make_play(&Play {
+            from: (3, 5),
+            to: (3, 4),
+        })
+        .unwrap();
+    assert_eq!(Some(Player::Defender), game_state.draw_offer());
+    let mut played_on = game_state.clone();
+    played_on
+        .make_play(&Play {
+            from: (0, 3),
+            to: (1, 3),
+        })
+        .unwrap();
+    assert_eq!(None, played_on.draw_offer());
+
+    assert_eq!(
+        Ok(GameStateUpdate::Draw),
+        game_state.accept_draw(Player::Attacker)
+    );
+    assert!(game_state.is_draw());
+    assert_eq!(
+        Some(GameResult {
+            winner: None,
+            reason: EndReason::Agreement,
+        }),
+        game_state.result()
+    );
+}
+
+#[test]
+fn repeating_a_position_three_times_ends_the_game() {
+    let shuffle = [
+        Play {
+            from: (3, 5),
+            to: (3, 4),
+        },
+        Play {
+            from: (0, 3),
+            to: (1, 3),
+        },
+        Play {
+            from: (3, 4),
+            to: (3, 5),
+        },
+        Play {
+            from: (1, 3),
+            to: (0, 3),
+        },
+    ];
+    let play_out = |repetition| {
+        let mut game_state = GameState::new(Rules {
+            repetition,
+            ..Rules::default()
+        });
+        let mut update = GameStateUpdate::Nothing;
+        // The position after the first play comes up again after the fifth and ninth
+        for play in shuffle.iter().cycle().take(9) {
+            assert!(!game_state.is_over());
+            update = game_state.make_play(play).unwrap();
+        }
+        (game_state, update)
+    };
+
+    let (drawn, update) = play_out(Repetition::Draw);
+    assert_eq!(GameStateUpdate::Draw, update);
+    assert_eq!(
+        Some(GameResult {
+            winner: None,
+            reason: EndReason::Repetition,
+        }),
+        drawn.result()
+    );
+
+    let (lost, update) = play_out(Repetition::DefendersLoss);
+    assert_eq!(GameStateUpdate::AttackerWin, update);
+    assert_eq!(Some(Player::Attacker), lost.winner());
+
+    let mut allowed = GameState::default();
+    for play in shuffle.iter().cycle().take(24) {
+        allowed.make_play(play).unwrap();
+    }
+    assert!(!allowed.is_over());
+}
+
+#[test]
+fn captures_are_spotted_without_making_the_play() {
+    for start in [
+        GameState::new(Rules::copenhagen()),
+        GameState::berserk().with_rules(Rules::berserk()),
+    ] {
+        let mut game_state = start;
+        let mut turn = 0;
+        while !game_state.is_over() && turn < 200 {
+            let plays = game_state.available_plays();
+            let mut capturing = None;
+            for play in &plays {
+                let mut copy = game_state.clone();
+                copy.make_play(play).unwrap();
+                let captured = !copy.captures.is_empty();
+                assert_eq!(captured, game_state.captures(play), "{:?}", play);
+                if captured {
+                    capturing = Some(play.clone());
+                }
+            }
+            // prefer capturing so the game gets through plenty of different positions
+            let play = capturing.unwrap_or_else(|| plays[(turn * 7) % plays.len()].clone());
+            game_state.make_play(&play).unwrap();
+            turn += 1;
+        }
+        assert!(!game_state.dead.is_empty());
+    }
 }
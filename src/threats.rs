@@ -0,0 +1,194 @@
+use crate::piece::Piece;
+use crate::state::{EndReason, GameState, Play, Player};
+
+use serde::{Deserialize, Serialize};
+
+/// What each side could do to the other with their next play
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Threats {
+    /// The king's plays straight to a corner, which it could make on the defenders' next turn
+    pub escape_routes: Vec<Play>,
+    /// The pieces of either side that the other could capture with their next play
+    pub in_danger: Vec<Danger>,
+    /// The attackers' plays that would capture the king on their next turn
+    pub king_captures: Vec<Play>,
+}
+
+/// A piece that could be captured, and the enemy plays that would capture it
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Danger {
+    pub position: (u8, u8),
+    pub piece: Piece,
+    pub by: Vec<Play>,
+}
+
+/// What players call out when the king has open paths to the corners
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize, uniffi::Enum)]
+pub enum KingCall {
+    /// The king has one open path to a corner, which the attackers must block
+    Raichi,
+    /// The king has two or more open paths to the corners, which can't all be blocked at once
+    Tuichi,
+}
+
+impl Threats {
+    /// Analyses the threats on the board, ignoring whose turn it is so that both sides'
+    /// next plays are considered.
+    pub fn analyse(game_state: &GameState) -> Self {
+        let mut threats = Threats {
+            escape_routes: escape_routes(game_state),
+            ..Threats::default()
+        };
+        if game_state.is_over() {
+            return threats;
+        }
+        for player in [Player::Attacker, Player::Defender] {
+            let lookahead = game_state.as_turn_of(player);
+            for play in lookahead.available_plays() {
+                let mut state = lookahead.clone();
+                state
+                    .make_play(&play)
+                    .expect("Using available plays should mean making a play never fails");
+                if state.end_reason() == Some(EndReason::KingCaptured) {
+                    threats.king_captures.push(play.clone());
+                }
+                for &(position, piece) in state.last_captures() {
+                    threats.add_danger(position, piece, &play);
+                }
+            }
+        }
+        threats
+    }
+
+    fn add_danger(&mut self, position: (u8, u8), piece: Piece, play: &Play) {
+        match self
+            .in_danger
+            .iter_mut()
+            .find(|danger| danger.position == position)
+        {
+            Some(danger) => danger.by.push(play.clone()),
+            None => self.in_danger.push(Danger {
+                position,
+                piece,
+                by: vec![play.clone()],
+            }),
+        }
+    }
+
+    /// Returns the call for the king's open paths to the corners, if it has any
+    pub fn king_call(&self) -> Option<KingCall> {
+        king_call(&self.escape_routes)
+    }
+
+    /// Returns true if the attackers could capture the king with their next play
+    pub fn king_in_danger(&self) -> bool {
+        !self.king_captures.is_empty()
+    }
+}
+
+/// Returns the king's plays straight to a corner, whether or not it is the defenders' turn.
+/// This is cheap enough to call while searching, unlike a full analysis.
+pub fn escape_routes(game_state: &GameState) -> Vec<Play> {
+    if game_state.is_over() {
+        return vec![];
+    }
+    game_state
+        .plays_from(game_state.king_position())
+        .into_iter()
+        .filter(|play| game_state.is_corner(play.to))
+        .collect()
+}
+
+/// Returns the call for the number of open paths to the corners, if there are any
+pub fn king_call(escape_routes: &[Play]) -> Option<KingCall> {
+    match escape_routes.len() {
+        0 => None,
+        1 => Some(KingCall::Raichi),
+        _ => Some(KingCall::Tuichi),
+    }
+}
+
+#[test]
+fn king_paths_to_the_corners_are_called() {
+    use easy_ml::matrices::Matrix;
+    #[rustfmt::skip]
+    let board = {
+        use crate::piece::Tile::Empty as E;
+        use crate::piece::Tile::Attacker as A;
+        use crate::piece::Tile::Defender as D;
+        use crate::piece::Tile::King as K;
+        Matrix::from_flat_row_major((11, 11), vec![
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            K, E, E, E, E, E, A, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            A, E, E, E, E, E, E, E, E, D, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+        ])
+    };
+    let raichi = GameState::from_setup(board.clone(), Player::Attacker, vec![]).unwrap();
+    assert_eq!(
+        vec![Play {
+            from: (0, 2),
+            to: (0, 0),
+        }],
+        escape_routes(&raichi)
+    );
+    assert_eq!(
+        Some(KingCall::Raichi),
+        Threats::analyse(&raichi).king_call()
+    );
+
+    let mut open = board;
+    open.set(8, 0, crate::piece::Tile::Empty);
+    let tuichi = GameState::from_setup(open, Player::Attacker, vec![]).unwrap();
+    assert_eq!(
+        Some(KingCall::Tuichi),
+        Threats::analyse(&tuichi).king_call()
+    );
+}
+
+#[test]
+fn pieces_in_danger_and_king_captures_are_found() {
+    use easy_ml::matrices::Matrix;
+    #[rustfmt::skip]
+    let board = {
+        use crate::piece::Tile::Empty as E;
+        use crate::piece::Tile::Attacker as A;
+        use crate::piece::Tile::Defender as D;
+        use crate::piece::Tile::King as K;
+        Matrix::from_flat_row_major((11, 11), vec![
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, A, D, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, E, E, E, E, E, E, E,
+            E, E, E, E, K, A, E, E, E, E, E,
+        ])
+    };
+    let game_state = GameState::from_setup(board, Player::Defender, vec![]).unwrap();
+    let threats = Threats::analyse(&game_state);
+    // The defender can be captured from the other side, by the attacker on the bottom edge
+    assert_eq!(
+        vec![Danger {
+            position: (4, 3),
+            piece: Piece::Defender,
+            by: vec![Play {
+                from: (5, 10),
+                to: (5, 3),
+            }],
+        }],
+        threats.in_danger
+    );
+    assert!(!threats.king_in_danger());
+}
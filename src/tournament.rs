@@ -0,0 +1,226 @@
+use crate::bot::random::random_play;
+use crate::bot::{Bot, Strategy};
+use crate::record::GameRecord;
+use crate::session::{Seat, Seats};
+use crate::state::{GameState, Play, Player};
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A series of bot vs bot games between two strategies
+#[derive(Clone, Debug)]
+pub struct Tournament {
+    pub first: Strategy,
+    pub second: Strategy,
+    pub games: u32,
+    /// Number of random plays made before the bots take over
+    pub random_openings: u32,
+    /// Number of turns after which an unfinished game is called a draw
+    pub max_turns: u32,
+    pub output: PathBuf,
+}
+
+/// A finished tournament game, as saved to disk
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TournamentGame {
+    pub attackers: Strategy,
+    pub defenders: Strategy,
+    /// How many of the plays in the record were random opening plays
+    pub opening: usize,
+    pub record: GameRecord,
+}
+
+/// Win, loss and draw counts from the perspective of the first strategy
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct Results {
+    wins: u32,
+    losses: u32,
+    draws: u32,
+    wins_as_attackers: u32,
+    wins_as_defenders: u32,
+    total_turns: u64,
+}
+
+impl Tournament {
+    /// Plays all the games in parallel, saving each to the output directory and printing
+    /// statistics once they have all finished.
+    pub fn run(&self) -> io::Result<()> {
+        fs::create_dir_all(&self.output)?;
+        // Each pair of games shares an opening with the sides swapped, so neither strategy
+        // gets a luckier start than the other
+        let openings: Vec<Vec<Play>> = (0..self.games.div_ceil(2))
+            .map(|_| random_opening(self.random_openings))
+            .collect();
+        let games: Vec<TournamentGame> = (0..self.games)
+            .into_par_iter()
+            .map(|i| {
+                let (attackers, defenders) = if i % 2 == 0 {
+                    (self.first, self.second)
+                } else {
+                    (self.second, self.first)
+                };
+                let opening = &openings[(i / 2) as usize];
+                let game = play_game(attackers, defenders, opening, self.max_turns);
+                println!(
+                    "Game {}: {} (Red) vs {} (White), {} after {} turns",
+                    i + 1,
+                    attackers,
+                    defenders,
+                    match game.record.winner {
+                        Some(winner) => format!("{} wins", winner),
+                        None => "draw".to_string(),
+                    },
+                    game.record.turn_count
+                );
+                game
+            })
+            .collect();
+        for (i, game) in games.iter().enumerate() {
+            let json = serde_json::to_string_pretty(game)
+                .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+            fs::write(self.output.join(format!("game-{:04}.json", i + 1)), json)?;
+        }
+        let results = self.results(&games);
+        println!();
+        println!("{} vs {}", self.first, self.second);
+        println!(
+            "Wins: {} ({} as Red, {} as White)",
+            results.wins, results.wins_as_attackers, results.wins_as_defenders
+        );
+        println!("Losses: {}", results.losses);
+        println!("Draws: {}", results.draws);
+        match elo_difference(results.wins, results.losses, results.draws) {
+            Some(elo) => println!("Elo difference: {:+.0}", elo),
+            None => println!("Elo difference: unbounded"),
+        }
+        if !games.is_empty() {
+            println!(
+                "Average game length: {:.1} turns",
+                results.total_turns as f64 / games.len() as f64
+            );
+        }
+        println!("Games saved to {}", self.output.display());
+        Ok(())
+    }
+
+    fn results(&self, games: &[TournamentGame]) -> Results {
+        let mut results = Results::default();
+        for (i, game) in games.iter().enumerate() {
+            results.total_turns += u64::from(game.record.turn_count);
+            // Compare by game index rather than strategy so mirror matches are still counted
+            let first_side = if i % 2 == 0 {
+                Player::Attacker
+            } else {
+                Player::Defender
+            };
+            match game.record.winner {
+                Some(winner) if winner == first_side => {
+                    results.wins += 1;
+                    match winner {
+                        Player::Attacker => results.wins_as_attackers += 1,
+                        Player::Defender => results.wins_as_defenders += 1,
+                    }
+                }
+                Some(_) => results.losses += 1,
+                None => results.draws += 1,
+            }
+        }
+        results
+    }
+}
+
+/// Makes up to the given number of random plays from the starting position, stopping early
+/// if the game ends.
+fn random_opening(plays: u32) -> Vec<Play> {
+    let mut game_state = GameState::default();
+    let mut opening = Vec::new();
+    for _ in 0..plays {
+        let play = match random_play(&game_state) {
+            Some(play) => play,
+            None => break,
+        };
+        game_state
+            .make_play(&play)
+            .expect("Using available plays should mean making a play never fails");
+        opening.push(play);
+        if game_state.winner().is_some() {
+            break;
+        }
+    }
+    opening
+}
+
+fn play_game(
+    attackers: Strategy,
+    defenders: Strategy,
+    opening: &[Play],
+    max_turns: u32,
+) -> TournamentGame {
+    let mut game_state = GameState::default();
+    let mut plays = Vec::new();
+    for play in opening {
+        game_state
+            .make_play(play)
+            .expect("Opening plays should replay from the starting position");
+        plays.push(play.clone());
+    }
+    let seats = Seats {
+        attackers: Seat::Bot(Bot::new(attackers)),
+        defenders: Seat::Bot(Bot::new(defenders)),
+    };
+    while game_state.turn_count() < max_turns {
+        match seats.step(&mut game_state) {
+            Some((play, _)) => plays.push(play),
+            None => break,
+        }
+    }
+    if !game_state.is_over() {
+        game_state
+            .reach_turn_limit()
+            .expect("The game can't be over while it is still being played");
+    }
+    TournamentGame {
+        attackers,
+        defenders,
+        opening: opening.len(),
+        record: GameRecord::new(plays, &game_state),
+    }
+}
+
+/// Estimates the Elo rating difference implied by a score, returning None if either side
+/// won every game as the difference is then unbounded.
+fn elo_difference(wins: u32, losses: u32, draws: u32) -> Option<f64> {
+    let games = wins + losses + draws;
+    if games == 0 {
+        return Some(0.0);
+    }
+    let score = (f64::from(wins) + 0.5 * f64::from(draws)) / f64::from(games);
+    if score <= 0.0 || score >= 1.0 {
+        return None;
+    }
+    Some(400.0 * (score / (1.0 - score)).log10())
+}
+
+#[test]
+fn elo_difference_is_symmetric() {
+    assert_eq!(Some(0.0), elo_difference(3, 3, 4));
+    let stronger = elo_difference(3, 1, 0).unwrap();
+    let weaker = elo_difference(1, 3, 0).unwrap();
+    assert!((stronger - 190.8).abs() < 0.1);
+    assert!((stronger + weaker).abs() < 1e-9);
+    assert_eq!(None, elo_difference(4, 0, 0));
+    assert_eq!(None, elo_difference(0, 4, 0));
+}
+
+#[test]
+fn tournament_games_replay_their_opening() {
+    let opening = random_opening(4);
+    let game = play_game(Strategy::Random, Strategy::Random, &opening, 10);
+    assert_eq!(opening.len(), game.opening);
+    assert_eq!(&opening[..], &game.record.plays[..opening.len()]);
+    assert!(game.record.turn_count <= 10);
+}
@@ -15,12 +15,16 @@ sealed interface Tile {
             uniffi.hnefatafl.Tile.ATTACKER -> Attacker
             uniffi.hnefatafl.Tile.DEFENDER -> Defender
             uniffi.hnefatafl.Tile.KING -> King
+            uniffi.hnefatafl.Tile.COMMANDER -> Commander
+            uniffi.hnefatafl.Tile.KNIGHT -> Knight
         }
 
         fun from(tile: Dead): Piece = when (tile) {
             Dead.ATTACKER -> Attacker
             Dead.DEFENDER -> Defender
             Dead.KING -> King
+            Dead.COMMANDER -> Commander
+            Dead.KNIGHT -> Knight
         }
     }
 
@@ -29,6 +33,8 @@ sealed interface Tile {
         Defender -> uniffi.hnefatafl.Tile.DEFENDER
         Empty -> uniffi.hnefatafl.Tile.EMPTY
         King -> uniffi.hnefatafl.Tile.KING
+        Commander -> uniffi.hnefatafl.Tile.COMMANDER
+        Knight -> uniffi.hnefatafl.Tile.KNIGHT
     }
 
     object Empty : Tile
@@ -47,4 +53,20 @@ sealed interface Tile {
         override fun ownedBy(player: Player) = player == Player.Defender
         override fun toDead() = Dead.KING
     }
+
+    /**
+     * An attacker that can capture by jumping, under the berserk rules
+     */
+    object Commander : Tile, Piece {
+        override fun ownedBy(player: Player) = player == Player.Attacker
+        override fun toDead() = Dead.COMMANDER
+    }
+
+    /**
+     * A defender that can capture by jumping, under the berserk rules
+     */
+    object Knight : Tile, Piece {
+        override fun ownedBy(player: Player) = player == Player.Defender
+        override fun toDead() = Dead.KNIGHT
+    }
 }
\ No newline at end of file
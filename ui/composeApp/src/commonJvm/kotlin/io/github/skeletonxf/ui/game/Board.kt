@@ -51,6 +51,7 @@ import io.github.skeletonxf.data.Position
 import io.github.skeletonxf.data.Tile
 import io.github.skeletonxf.data.TileColor
 import io.github.skeletonxf.ui.Res
+import io.github.skeletonxf.ui.champion
 import io.github.skeletonxf.ui.king
 import io.github.skeletonxf.ui.piece
 import io.github.skeletonxf.ui.strings.LocalStrings
@@ -415,6 +416,8 @@ fun Piece.tint(): Color = when (this) {
     Tile.Attacker -> HnefataflColors.brown
     Tile.Defender -> HnefataflColors.night
     Tile.King -> HnefataflColors.night
+    Tile.Commander -> HnefataflColors.brown
+    Tile.Knight -> HnefataflColors.night
 }
 
 @Composable
@@ -443,6 +446,20 @@ fun Piece.Icon(
             modifier = modifier,
             tint = this.tint(),
         )
+
+        Tile.Commander -> Icon(
+            painter = painterResource(Res.drawable.champion),
+            contentDescription = strings.commander,
+            modifier = modifier,
+            tint = this.tint(),
+        )
+
+        Tile.Knight -> Icon(
+            painter = painterResource(Res.drawable.champion),
+            contentDescription = strings.knight,
+            modifier = modifier,
+            tint = this.tint(),
+        )
     }
 }
 
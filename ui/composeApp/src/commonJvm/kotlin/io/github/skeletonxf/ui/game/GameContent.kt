@@ -342,10 +342,9 @@ private fun Title(
     modifier: Modifier = Modifier,
 ) {
     val strings = LocalStrings.current.game
-    val turnsTaken = when (turn) {
-        Player.Defender -> turnCount / 2u
-        Player.Attacker -> (if (turnCount > 0u) turnCount - 1u else turnCount) / 2u
-    }
+    // Each side has made the same number of plays when it's the first player's turn, and the
+    // first player has made one more otherwise, so either way this is the turn player's count
+    val turnsTaken = turnCount / 2u
     Text(
         text = when (winner) {
             Winner.None -> when (turn) {
@@ -77,6 +77,8 @@ data class Strings(
             val attacker: String = "Attacker",
             val defender: String = "Defender",
             val king: String = "King",
+            val commander: String = "Commander",
+            val knight: String = "Knight",
         )
     }
 
@@ -151,6 +153,8 @@ private val castilianSpanish = Strings(
             attacker = "Atacante",
             defender = "Defensor",
             king = "Rey",
+            commander = "Comandante",
+            knight = "Caballero",
         )
     ),
     component = Strings.Component(